@@ -0,0 +1,655 @@
+#[cfg(not(feature = "std"))]
+use crate::chacha20::chacha20_encrypt_in_place;
+#[cfg(feature = "std")]
+use crate::chacha20::chacha20_encrypt_parallel;
+use crate::cipher::{xchacha20_subkey, Key, Nonce, XNonce};
+use crate::error::ChaChaError;
+use crate::poly1305::{poly1305_key_gen, Poly1305};
+use crate::wipe::wipe;
+#[cfg(test)]
+use alloc::vec;
+#[cfg(any(feature = "alloc", test))]
+use alloc::vec::Vec;
+
+// 2.8.  AEAD Construction
+
+//    AEAD_CHACHA20_POLY1305 is an authenticated encryption with additional
+//    data algorithm.  The inputs to AEAD_CHACHA20_POLY1305 are:
+
+//    o  A 256-bit key
+//    o  A 96-bit nonce -- different for each invocation with the same key
+//    o  An arbitrary length plaintext
+//    o  Arbitrary length additional authenticated data (AAD)
+
+const TAG_LEN: usize = 16;
+
+/// The AEAD_CHACHA20_POLY1305 construction of RFC 8439 section 2.8.
+///
+/// `seal` returns the ciphertext followed by the 16-byte tag, and `open`
+/// takes the same layout back.  `seal_in_place` and `open_in_place` work on
+/// a caller's buffer with the tag kept separately, and need no allocator.
+pub struct ChaCha20Poly1305 {
+    key: Key,
+}
+
+impl ChaCha20Poly1305 {
+    pub fn new(key: &Key) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305 { key: key.clone() }
+    }
+
+    /// Encrypts and authenticates `plaintext`, and authenticates `aad`.
+    ///
+    /// Fails with `CounterExhausted` if `plaintext` is longer than the
+    /// 256 GiB the block counter can cover.
+    #[cfg(feature = "alloc")]
+    pub fn seal(
+        &self,
+        nonce: &Nonce,
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, ChaChaError> {
+        seal(&self.key, nonce, aad, plaintext, 1)
+    }
+
+    /// Same as `seal`, but a large `plaintext` is encrypted on up to
+    /// `threads` threads (0 for one per CPU).  Only the encryption is split;
+    /// the Poly1305 tag is computed on the calling thread.
+    #[cfg(feature = "std")]
+    pub fn seal_parallel(
+        &self,
+        nonce: &Nonce,
+        aad: &[u8],
+        plaintext: &[u8],
+        threads: usize,
+    ) -> Result<Vec<u8>, ChaChaError> {
+        seal(&self.key, nonce, aad, plaintext, threads)
+    }
+
+    /// Encrypts `buffer` in place, authenticates it and `aad`, and returns
+    /// the tag.
+    pub fn seal_in_place(
+        &self,
+        nonce: &Nonce,
+        aad: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<[u8; TAG_LEN], ChaChaError> {
+        seal_in_place(&self.key, nonce, aad, buffer, 1)
+    }
+
+    /// Verifies and decrypts the output of `seal`.  Fails with
+    /// `AuthenticationFailed` without decrypting anything if the tag does not
+    /// match.
+    #[cfg(feature = "alloc")]
+    pub fn open(
+        &self,
+        nonce: &Nonce,
+        aad: &[u8],
+        ciphertext_and_tag: &[u8],
+    ) -> Result<Vec<u8>, ChaChaError> {
+        open(&self.key, nonce, aad, ciphertext_and_tag, 1)
+    }
+
+    /// Same as `open`, but a large ciphertext is decrypted on up to `threads`
+    /// threads (0 for one per CPU).
+    #[cfg(feature = "std")]
+    pub fn open_parallel(
+        &self,
+        nonce: &Nonce,
+        aad: &[u8],
+        ciphertext_and_tag: &[u8],
+        threads: usize,
+    ) -> Result<Vec<u8>, ChaChaError> {
+        open(&self.key, nonce, aad, ciphertext_and_tag, threads)
+    }
+
+    /// Verifies `tag` and decrypts `buffer` in place.  Fails with
+    /// `AuthenticationFailed`, leaving `buffer` untouched, if the tag does
+    /// not match.
+    pub fn open_in_place(
+        &self,
+        nonce: &Nonce,
+        aad: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8; TAG_LEN],
+    ) -> Result<(), ChaChaError> {
+        open_in_place(&self.key, nonce, aad, buffer, tag, 1)
+    }
+}
+
+/// XChaCha20-Poly1305 from draft-irtf-cfrg-xchacha, compatible with
+/// libsodium's `crypto_aead_xchacha20poly1305_ietf`.
+///
+/// The 192-bit nonce is long enough to be generated at random.  Output
+/// layout is the same as `ChaCha20Poly1305`.
+pub struct XChaCha20Poly1305 {
+    key: Key,
+}
+
+impl XChaCha20Poly1305 {
+    pub fn new(key: &Key) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305 { key: key.clone() }
+    }
+
+    /// Encrypts and authenticates `plaintext`, and authenticates `aad`.
+    #[cfg(feature = "alloc")]
+    pub fn seal(
+        &self,
+        nonce: &XNonce,
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, ChaChaError> {
+        let (subkey, chacha_nonce) = xchacha20_subkey(&self.key, nonce);
+        seal(&subkey, &chacha_nonce, aad, plaintext, 1)
+    }
+
+    /// See `ChaCha20Poly1305::seal_parallel`.
+    #[cfg(feature = "std")]
+    pub fn seal_parallel(
+        &self,
+        nonce: &XNonce,
+        aad: &[u8],
+        plaintext: &[u8],
+        threads: usize,
+    ) -> Result<Vec<u8>, ChaChaError> {
+        let (subkey, chacha_nonce) = xchacha20_subkey(&self.key, nonce);
+        seal(&subkey, &chacha_nonce, aad, plaintext, threads)
+    }
+
+    /// See `ChaCha20Poly1305::seal_in_place`.
+    pub fn seal_in_place(
+        &self,
+        nonce: &XNonce,
+        aad: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<[u8; TAG_LEN], ChaChaError> {
+        let (subkey, chacha_nonce) = xchacha20_subkey(&self.key, nonce);
+        seal_in_place(&subkey, &chacha_nonce, aad, buffer, 1)
+    }
+
+    /// Verifies and decrypts the output of `seal`.  Fails with
+    /// `AuthenticationFailed` without decrypting anything if the tag does not
+    /// match.
+    #[cfg(feature = "alloc")]
+    pub fn open(
+        &self,
+        nonce: &XNonce,
+        aad: &[u8],
+        ciphertext_and_tag: &[u8],
+    ) -> Result<Vec<u8>, ChaChaError> {
+        let (subkey, chacha_nonce) = xchacha20_subkey(&self.key, nonce);
+        open(&subkey, &chacha_nonce, aad, ciphertext_and_tag, 1)
+    }
+
+    /// See `ChaCha20Poly1305::open_parallel`.
+    #[cfg(feature = "std")]
+    pub fn open_parallel(
+        &self,
+        nonce: &XNonce,
+        aad: &[u8],
+        ciphertext_and_tag: &[u8],
+        threads: usize,
+    ) -> Result<Vec<u8>, ChaChaError> {
+        let (subkey, chacha_nonce) = xchacha20_subkey(&self.key, nonce);
+        open(&subkey, &chacha_nonce, aad, ciphertext_and_tag, threads)
+    }
+
+    /// See `ChaCha20Poly1305::open_in_place`.
+    pub fn open_in_place(
+        &self,
+        nonce: &XNonce,
+        aad: &[u8],
+        buffer: &mut [u8],
+        tag: &[u8; TAG_LEN],
+    ) -> Result<(), ChaChaError> {
+        let (subkey, chacha_nonce) = xchacha20_subkey(&self.key, nonce);
+        open_in_place(&subkey, &chacha_nonce, aad, buffer, tag, 1)
+    }
+}
+
+// `threads` is passed to `chacha20_encrypt_parallel`; 1 encrypts serially.
+#[cfg(feature = "alloc")]
+fn seal(
+    key: &Key,
+    nonce: &Nonce,
+    aad: &[u8],
+    plaintext: &[u8],
+    threads: usize,
+) -> Result<Vec<u8>, ChaChaError> {
+    let mut out = Vec::with_capacity(plaintext.len() + TAG_LEN);
+    out.extend_from_slice(plaintext);
+    let tag = seal_in_place(key, nonce, aad, &mut out, threads)?;
+    out.extend_from_slice(&tag);
+    Ok(out)
+}
+
+#[cfg(feature = "alloc")]
+fn open(
+    key: &Key,
+    nonce: &Nonce,
+    aad: &[u8],
+    ciphertext_and_tag: &[u8],
+    threads: usize,
+) -> Result<Vec<u8>, ChaChaError> {
+    if ciphertext_and_tag.len() < TAG_LEN {
+        return Err(ChaChaError::AuthenticationFailed);
+    }
+    let (ciphertext, tag) = ciphertext_and_tag.split_at(ciphertext_and_tag.len() - TAG_LEN);
+    let mut tag_bytes = [0u8; TAG_LEN];
+    tag_bytes.copy_from_slice(tag);
+
+    let mut plaintext = ciphertext.to_vec();
+    open_in_place(key, nonce, aad, &mut plaintext, &tag_bytes, threads)?;
+    Ok(plaintext)
+}
+
+fn seal_in_place(
+    key: &Key,
+    nonce: &Nonce,
+    aad: &[u8],
+    buffer: &mut [u8],
+    threads: usize,
+) -> Result<[u8; TAG_LEN], ChaChaError> {
+    //    o  First, a Poly1305 one-time key is generated from the 256-bit
+    //       key and nonce using the procedure described in Section 2.6.
+    let mut otk = poly1305_key_gen(key, nonce);
+
+    //    o  Next, the ChaCha20 encryption function is called to encrypt
+    //       the plaintext, using the same key and nonce, and with the
+    //       initial counter set to 1.
+    let tag = encrypt(key, nonce, buffer, threads).map(|()| compute_tag(&otk, aad, buffer));
+    wipe(&mut otk);
+
+    tag
+}
+
+fn open_in_place(
+    key: &Key,
+    nonce: &Nonce,
+    aad: &[u8],
+    buffer: &mut [u8],
+    tag: &[u8; TAG_LEN],
+    threads: usize,
+) -> Result<(), ChaChaError> {
+    let mut otk = poly1305_key_gen(key, nonce);
+    let authentic = constant_time_eq(&compute_tag(&otk, aad, buffer), tag);
+    wipe(&mut otk);
+    if !authentic {
+        return Err(ChaChaError::AuthenticationFailed);
+    }
+
+    encrypt(key, nonce, buffer, threads)
+}
+
+// ChaCha20 from block 1, on up to `threads` threads when std is available.
+fn encrypt(key: &Key, nonce: &Nonce, data: &mut [u8], threads: usize) -> Result<(), ChaChaError> {
+    #[cfg(feature = "std")]
+    {
+        chacha20_encrypt_parallel(key.as_bytes(), 1, nonce.as_bytes(), data, threads)
+    }
+
+    #[cfg(not(feature = "std"))]
+    {
+        debug_assert_eq!(threads, 1);
+        chacha20_encrypt_in_place(key.as_bytes(), 1, nonce.as_bytes(), data)
+    }
+}
+
+//    o  Finally, the Poly1305 function is called with the Poly1305 key
+//       calculated above, and a message constructed as a concatenation of
+//       the following:
+//       *  The AAD
+//       *  padding1 -- the padding is up to 15 zero bytes, and it brings
+//          the total length so far to an integral multiple of 16.
+//       *  The ciphertext
+//       *  padding2 -- the padding is up to 15 zero bytes, and it brings
+//          the total length so far to an integral multiple of 16.
+//       *  The length of the additional data in octets (as a 64-bit
+//          little-endian integer).
+//       *  The length of the ciphertext in octets (as a 64-bit little-
+//          endian integer).
+fn compute_tag(otk: &[u8; 32], aad: &[u8], ciphertext: &[u8]) -> [u8; 16] {
+    let padding = [0u8; 16];
+    let mut mac = Poly1305::new(otk);
+
+    mac.update(aad);
+    mac.update(&padding[..(16 - aad.len() % 16) % 16]);
+    mac.update(ciphertext);
+    mac.update(&padding[..(16 - ciphertext.len() % 16) % 16]);
+    mac.update(&(aad.len() as u64).to_le_bytes());
+    mac.update(&(ciphertext.len() as u64).to_le_bytes());
+
+    mac.finalize()
+}
+
+/// Compares the whole of two secrets or tags regardless of where the first
+/// difference is, so that the time taken does not tell an attacker how much
+/// of a forgery was right.  Only the lengths, which are public, may end the
+/// comparison early.
+pub fn constant_time_eq(expected: &[u8], actual: &[u8]) -> bool {
+    if expected.len() != actual.len() {
+        return false;
+    }
+
+    let mut diff = 0u8;
+    for (a, b) in expected.iter().zip(actual.iter()) {
+        diff |= a ^ b;
+    }
+
+    core::hint::black_box(diff) == 0
+}
+
+#[cfg(test)]
+const SUNSCREEN: &[u8] = b"Ladies and Gentlemen of the class of '99: \
+If I could offer you only one tip for the future, sunscreen would be it.";
+
+// 2.8.2.  Example and Test Vector for AEAD_CHACHA20_POLY1305
+
+#[cfg(test)]
+fn sunscreen_vector() -> (ChaCha20Poly1305, Nonce, Vec<u8>, Vec<u8>) {
+    // Key:
+    // 000  80 81 82 83 84 85 86 87 88 89 8a 8b 8c 8d 8e 8f  ................
+    // 016  90 91 92 93 94 95 96 97 98 99 9a 9b 9c 9d 9e 9f  ................
+    let mut key = [0u8; 32];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = 0x80 + i as u8;
+    }
+
+    // AAD:
+    // 000  50 51 52 53 c0 c1 c2 c3 c4 c5 c6 c7              PQRS........
+    let aad = vec![
+        0x50, 0x51, 0x52, 0x53, 0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7,
+    ];
+
+    // 32-bit fixed-common part:
+    // 000  07 00 00 00                                      ....
+    // IV:
+    // 000  40 41 42 43 44 45 46 47                          @ABCDEFG
+    let nonce = Nonce::new([
+        0x07, 0x00, 0x00, 0x00, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47,
+    ]);
+
+    // Ciphertext followed by Tag: 1a:e1:0b:59:4f:09:e2:6a:7e:90:2e:cb:d0:60:06:91
+    let sealed = vec![
+        0xd3, 0x1a, 0x8d, 0x34, 0x64, 0x8e, 0x60, 0xdb, 0x7b, 0x86, 0xaf, 0xbc, 0x53, 0xef, 0x7e,
+        0xc2, 0xa4, 0xad, 0xed, 0x51, 0x29, 0x6e, 0x08, 0xfe, 0xa9, 0xe2, 0xb5, 0xa7, 0x36, 0xee,
+        0x62, 0xd6, 0x3d, 0xbe, 0xa4, 0x5e, 0x8c, 0xa9, 0x67, 0x12, 0x82, 0xfa, 0xfb, 0x69, 0xda,
+        0x92, 0x72, 0x8b, 0x1a, 0x71, 0xde, 0x0a, 0x9e, 0x06, 0x0b, 0x29, 0x05, 0xd6, 0xa5, 0xb6,
+        0x7e, 0xcd, 0x3b, 0x36, 0x92, 0xdd, 0xbd, 0x7f, 0x2d, 0x77, 0x8b, 0x8c, 0x98, 0x03, 0xae,
+        0xe3, 0x28, 0x09, 0x1b, 0x58, 0xfa, 0xb3, 0x24, 0xe4, 0xfa, 0xd6, 0x75, 0x94, 0x55, 0x85,
+        0x80, 0x8b, 0x48, 0x31, 0xd7, 0xbc, 0x3f, 0xf4, 0xde, 0xf0, 0x8e, 0x4b, 0x7a, 0x9d, 0xe5,
+        0x76, 0xd2, 0x65, 0x86, 0xce, 0xc6, 0x4b, 0x61, 0x16, // end of ciphertext
+        0x1a, 0xe1, 0x0b, 0x59, 0x4f, 0x09, 0xe2, 0x6a, 0x7e, 0x90, 0x2e, 0xcb, 0xd0, 0x60, 0x06,
+        0x91,
+    ];
+
+    (ChaCha20Poly1305::new(&Key::new(key)), nonce, aad, sealed)
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_seal() {
+    let (aead, nonce, aad, sealed) = sunscreen_vector();
+
+    assert_eq!(aead.seal(&nonce, &aad, SUNSCREEN).unwrap(), sealed);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_open() {
+    let (aead, nonce, aad, sealed) = sunscreen_vector();
+
+    assert_eq!(aead.open(&nonce, &aad, &sealed), Ok(SUNSCREEN.to_vec()));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_open_rejects_tampering() {
+    let (aead, nonce, aad, sealed) = sunscreen_vector();
+
+    for i in 0..sealed.len() {
+        let mut tampered = sealed.clone();
+        tampered[i] ^= 0x01;
+        assert_eq!(
+            aead.open(&nonce, &aad, &tampered),
+            Err(ChaChaError::AuthenticationFailed),
+            "byte {}",
+            i
+        );
+    }
+
+    let mut tampered_aad = aad.clone();
+    tampered_aad[0] ^= 0x80;
+    assert_eq!(
+        aead.open(&nonce, &tampered_aad, &sealed),
+        Err(ChaChaError::AuthenticationFailed)
+    );
+
+    let mut other_nonce = *nonce.as_bytes();
+    other_nonce[0] ^= 0x01;
+    assert_eq!(
+        aead.open(&Nonce::new(other_nonce), &aad, &sealed),
+        Err(ChaChaError::AuthenticationFailed)
+    );
+
+    assert_eq!(
+        aead.open(&nonce, &aad, &sealed[..sealed.len() - 1]),
+        Err(ChaChaError::AuthenticationFailed)
+    );
+    assert_eq!(
+        aead.open(&nonce, &aad, &sealed[..15]),
+        Err(ChaChaError::AuthenticationFailed)
+    );
+}
+
+#[test]
+fn test_seal_and_open_in_place() {
+    let (aead, nonce, aad, sealed) = sunscreen_vector();
+    let (ciphertext, tag) = sealed.split_at(SUNSCREEN.len());
+
+    let mut buffer = [0u8; 114];
+    buffer.copy_from_slice(SUNSCREEN);
+    let actual_tag = aead.seal_in_place(&nonce, &aad, &mut buffer).unwrap();
+    assert_eq!(buffer[..], ciphertext[..]);
+    assert_eq!(actual_tag[..], tag[..]);
+
+    // A bad tag leaves the buffer alone.
+    let mut bad_tag = actual_tag;
+    bad_tag[15] ^= 0x80;
+    assert_eq!(
+        aead.open_in_place(&nonce, &aad, &mut buffer, &bad_tag),
+        Err(ChaChaError::AuthenticationFailed)
+    );
+    assert_eq!(buffer[..], ciphertext[..]);
+
+    aead.open_in_place(&nonce, &aad, &mut buffer, &actual_tag)
+        .unwrap();
+    assert_eq!(buffer[..], SUNSCREEN[..]);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_empty_plaintext() {
+    let (aead, nonce, aad, _) = sunscreen_vector();
+
+    let sealed = aead.seal(&nonce, &aad, &[]).unwrap();
+    assert_eq!(sealed.len(), TAG_LEN);
+    assert_eq!(aead.open(&nonce, &aad, &sealed), Ok(vec![]));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_seal_parallel() {
+    let (aead, nonce, aad, _) = sunscreen_vector();
+    let plaintext: Vec<u8> = (0..200 * 1024 + 3).map(|i| i as u8).collect();
+
+    let sealed = aead.seal(&nonce, &aad, &plaintext).unwrap();
+    assert!(aead.seal_parallel(&nonce, &aad, &plaintext, 4).unwrap() == sealed);
+    assert_eq!(aead.open_parallel(&nonce, &aad, &sealed, 4), Ok(plaintext));
+
+    let mut tampered = sealed;
+    tampered[100_000] ^= 0x01;
+    assert_eq!(
+        aead.open_parallel(&nonce, &aad, &tampered, 4),
+        Err(ChaChaError::AuthenticationFailed)
+    );
+}
+
+// A.5.  ChaCha20-Poly1305 AEAD Decryption
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_open_internet_drafts() {
+    let key = [
+        0x1c, 0x92, 0x40, 0xa5, 0xeb, 0x55, 0xd3, 0x8a, 0xf3, 0x33, 0x88, 0x86, 0x04, 0xf6, 0xb5,
+        0xf0, 0x47, 0x39, 0x17, 0xc1, 0x40, 0x2b, 0x80, 0x09, 0x9d, 0xca, 0x5c, 0xbc, 0x20, 0x70,
+        0x75, 0xc0,
+    ];
+    let nonce = [
+        0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+    ];
+    let aad = [
+        0xf3, 0x33, 0x88, 0x86, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4e, 0x91,
+    ];
+    let sealed = [
+        0x64, 0xa0, 0x86, 0x15, 0x75, 0x86, 0x1a, 0xf4, 0x60, 0xf0, 0x62, 0xc7, 0x9b, 0xe6, 0x43,
+        0xbd, 0x5e, 0x80, 0x5c, 0xfd, 0x34, 0x5c, 0xf3, 0x89, 0xf1, 0x08, 0x67, 0x0a, 0xc7, 0x6c,
+        0x8c, 0xb2, 0x4c, 0x6c, 0xfc, 0x18, 0x75, 0x5d, 0x43, 0xee, 0xa0, 0x9e, 0xe9, 0x4e, 0x38,
+        0x2d, 0x26, 0xb0, 0xbd, 0xb7, 0xb7, 0x3c, 0x32, 0x1b, 0x01, 0x00, 0xd4, 0xf0, 0x3b, 0x7f,
+        0x35, 0x58, 0x94, 0xcf, 0x33, 0x2f, 0x83, 0x0e, 0x71, 0x0b, 0x97, 0xce, 0x98, 0xc8, 0xa8,
+        0x4a, 0xbd, 0x0b, 0x94, 0x81, 0x14, 0xad, 0x17, 0x6e, 0x00, 0x8d, 0x33, 0xbd, 0x60, 0xf9,
+        0x82, 0xb1, 0xff, 0x37, 0xc8, 0x55, 0x97, 0x97, 0xa0, 0x6e, 0xf4, 0xf0, 0xef, 0x61, 0xc1,
+        0x86, 0x32, 0x4e, 0x2b, 0x35, 0x06, 0x38, 0x36, 0x06, 0x90, 0x7b, 0x6a, 0x7c, 0x02, 0xb0,
+        0xf9, 0xf6, 0x15, 0x7b, 0x53, 0xc8, 0x67, 0xe4, 0xb9, 0x16, 0x6c, 0x76, 0x7b, 0x80, 0x4d,
+        0x46, 0xa5, 0x9b, 0x52, 0x16, 0xcd, 0xe7, 0xa4, 0xe9, 0x90, 0x40, 0xc5, 0xa4, 0x04, 0x33,
+        0x22, 0x5e, 0xe2, 0x82, 0xa1, 0xb0, 0xa0, 0x6c, 0x52, 0x3e, 0xaf, 0x45, 0x34, 0xd7, 0xf8,
+        0x3f, 0xa1, 0x15, 0x5b, 0x00, 0x47, 0x71, 0x8c, 0xbc, 0x54, 0x6a, 0x0d, 0x07, 0x2b, 0x04,
+        0xb3, 0x56, 0x4e, 0xea, 0x1b, 0x42, 0x22, 0x73, 0xf5, 0x48, 0x27, 0x1a, 0x0b, 0xb2, 0x31,
+        0x60, 0x53, 0xfa, 0x76, 0x99, 0x19, 0x55, 0xeb, 0xd6, 0x31, 0x59, 0x43, 0x4e, 0xce, 0xbb,
+        0x4e, 0x46, 0x6d, 0xae, 0x5a, 0x10, 0x73, 0xa6, 0x72, 0x76, 0x27, 0x09, 0x7a, 0x10, 0x49,
+        0xe6, 0x17, 0xd9, 0x1d, 0x36, 0x10, 0x94, 0xfa, 0x68, 0xf0, 0xff, 0x77, 0x98, 0x71, 0x30,
+        0x30, 0x5b, 0xea, 0xba, 0x2e, 0xda, 0x04, 0xdf, 0x99, 0x7b, 0x71, 0x4d, 0x6c, 0x6f, 0x2c,
+        0x29, 0xa6, 0xad, 0x5c, 0xb4, 0x02, 0x2b, 0x02, 0x70, 0x9b, // end of ciphertext
+        0xee, 0xad, 0x9d, 0x67, 0x89, 0x0c, 0xbb, 0x22, 0x39, 0x23, 0x36, 0xfe, 0xa1, 0x85, 0x1f,
+        0x38,
+    ];
+    let expected = "Internet-Drafts are draft documents valid for a maximum of six months \
+and may be updated, replaced, or obsoleted by other documents at any time. It is inappropriate \
+to use Internet-Drafts as reference material or to cite them other than as /\u{201c}work in \
+progress./\u{201d}";
+
+    let aead = ChaCha20Poly1305::new(&Key::new(key));
+    let plaintext = aead.open(&Nonce::new(nonce), &aad, &sealed);
+
+    assert_eq!(plaintext, Ok(expected.as_bytes().to_vec()));
+}
+
+// draft-irtf-cfrg-xchacha, A.3.1.  Example and Test Vector for
+// AEAD_XChaCha20_Poly1305
+
+#[cfg(test)]
+fn xchacha_sunscreen_vector() -> (XChaCha20Poly1305, XNonce, Vec<u8>, Vec<u8>) {
+    // Key: 808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f
+    let mut key = [0u8; 32];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = 0x80 + i as u8;
+    }
+
+    // AAD: 50515253c0c1c2c3c4c5c6c7
+    let aad = vec![
+        0x50, 0x51, 0x52, 0x53, 0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7,
+    ];
+
+    // IV: 404142434445464748494a4b4c4d4e4f5051525354555657
+    let nonce = XNonce::new([
+        0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e,
+        0x4f, 0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57,
+    ]);
+
+    // Ciphertext followed by Tag: c0875924c1c7987947deafd8780acf49
+    let sealed = vec![
+        0xbd, 0x6d, 0x17, 0x9d, 0x3e, 0x83, 0xd4, 0x3b, 0x95, 0x76, 0x57, 0x94, 0x93, 0xc0, 0xe9,
+        0x39, 0x57, 0x2a, 0x17, 0x00, 0x25, 0x2b, 0xfa, 0xcc, 0xbe, 0xd2, 0x90, 0x2c, 0x21, 0x39,
+        0x6c, 0xbb, 0x73, 0x1c, 0x7f, 0x1b, 0x0b, 0x4a, 0xa6, 0x44, 0x0b, 0xf3, 0xa8, 0x2f, 0x4e,
+        0xda, 0x7e, 0x39, 0xae, 0x64, 0xc6, 0x70, 0x8c, 0x54, 0xc2, 0x16, 0xcb, 0x96, 0xb7, 0x2e,
+        0x12, 0x13, 0xb4, 0x52, 0x2f, 0x8c, 0x9b, 0xa4, 0x0d, 0xb5, 0xd9, 0x45, 0xb1, 0x1b, 0x69,
+        0xb9, 0x82, 0xc1, 0xbb, 0x9e, 0x3f, 0x3f, 0xac, 0x2b, 0xc3, 0x69, 0x48, 0x8f, 0x76, 0xb2,
+        0x38, 0x35, 0x65, 0xd3, 0xff, 0xf9, 0x21, 0xf9, 0x66, 0x4c, 0x97, 0x63, 0x7d, 0xa9, 0x76,
+        0x88, 0x12, 0xf6, 0x15, 0xc6, 0x8b, 0x13, 0xb5, 0x2e, // end of ciphertext
+        0xc0, 0x87, 0x59, 0x24, 0xc1, 0xc7, 0x98, 0x79, 0x47, 0xde, 0xaf, 0xd8, 0x78, 0x0a, 0xcf,
+        0x49,
+    ];
+
+    (XChaCha20Poly1305::new(&Key::new(key)), nonce, aad, sealed)
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_xchacha_seal() {
+    let (aead, nonce, aad, sealed) = xchacha_sunscreen_vector();
+
+    assert_eq!(aead.seal(&nonce, &aad, SUNSCREEN).unwrap(), sealed);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_xchacha_open() {
+    let (aead, nonce, aad, sealed) = xchacha_sunscreen_vector();
+
+    assert_eq!(aead.open(&nonce, &aad, &sealed), Ok(SUNSCREEN.to_vec()));
+}
+
+#[test]
+fn test_xchacha_seal_and_open_in_place() {
+    let (aead, nonce, aad, sealed) = xchacha_sunscreen_vector();
+    let (ciphertext, tag) = sealed.split_at(SUNSCREEN.len());
+
+    let mut buffer = [0u8; 114];
+    buffer.copy_from_slice(SUNSCREEN);
+    let actual_tag = aead.seal_in_place(&nonce, &aad, &mut buffer).unwrap();
+    assert_eq!(buffer[..], ciphertext[..]);
+    assert_eq!(actual_tag[..], tag[..]);
+
+    aead.open_in_place(&nonce, &aad, &mut buffer, &actual_tag)
+        .unwrap();
+    assert_eq!(buffer[..], SUNSCREEN[..]);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_xchacha_open_rejects_tampering() {
+    let (aead, nonce, aad, sealed) = xchacha_sunscreen_vector();
+
+    for i in 0..sealed.len() {
+        let mut tampered = sealed.clone();
+        tampered[i] ^= 0x01;
+        assert_eq!(
+            aead.open(&nonce, &aad, &tampered),
+            Err(ChaChaError::AuthenticationFailed),
+            "byte {}",
+            i
+        );
+    }
+
+    // Every part of the extended nonce feeds into the key or the nonce.
+    for i in 0..24 {
+        let mut other_nonce = *nonce.as_bytes();
+        other_nonce[i] ^= 0x01;
+        assert_eq!(
+            aead.open(&XNonce::new(other_nonce), &aad, &sealed),
+            Err(ChaChaError::AuthenticationFailed),
+            "nonce byte {}",
+            i
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_xchacha_seal_parallel() {
+    let (aead, nonce, aad, _) = xchacha_sunscreen_vector();
+    let plaintext = vec![0x5a; 300 * 1024];
+
+    let sealed = aead.seal(&nonce, &aad, &plaintext).unwrap();
+    assert!(aead.seal_parallel(&nonce, &aad, &plaintext, 0).unwrap() == sealed);
+    assert_eq!(aead.open_parallel(&nonce, &aad, &sealed, 3), Ok(plaintext));
+}
@@ -0,0 +1,349 @@
+// Key stream generation for many blocks at once.  The portable code makes
+// four interleaved blocks at a time in plain Rust; on x86_64 the SIMD
+// backends compute 4 (SSE2), 8 (AVX2) or, with the `avx512` feature, 16
+// (AVX-512F) blocks side by side, one block per vector lane.  The best
+// backend is picked at run time, or at build time without std.
+//
+// Every backend treats words 12-13 as a 64-bit little-endian block counter.
+// For the RFC 8439 layout that is the same as the 32-bit counter in word 12,
+// because callers never go past counter 2^32 - 1.
+
+use crate::chacha20::chacha_blocks4;
+use crate::wipe::wipe;
+
+#[cfg(target_arch = "x86_64")]
+mod avx2;
+#[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+mod avx512;
+#[cfg(target_arch = "x86_64")]
+mod sse2;
+
+// Whether the CPU has a target feature.  With std this is checked at run
+// time; without it, only features the build enables (for example with
+// `-C target-cpu`) are used.
+#[cfg(all(target_arch = "x86_64", any(feature = "std", test)))]
+macro_rules! has_feature {
+    ($feature:tt) => {
+        std::is_x86_feature_detected!($feature)
+    };
+}
+#[cfg(all(target_arch = "x86_64", not(any(feature = "std", test))))]
+macro_rules! has_feature {
+    ($feature:tt) => {
+        cfg!(target_feature = $feature)
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Backend {
+    Portable,
+    #[cfg(target_arch = "x86_64")]
+    Sse2,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+    #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+    Avx512,
+}
+
+impl Backend {
+    // The fastest backend the CPU supports.
+    pub(crate) fn detect() -> Backend {
+        #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+        {
+            if has_feature!("avx512f") {
+                return Backend::Avx512;
+            }
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            if has_feature!("avx2") {
+                return Backend::Avx2;
+            }
+            if has_feature!("sse2") {
+                return Backend::Sse2;
+            }
+        }
+
+        Backend::Portable
+    }
+
+    // Every backend the CPU supports, for cross-checking.
+    #[cfg(test)]
+    pub(crate) fn available() -> Vec<Backend> {
+        let mut backends = vec![Backend::Portable];
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            if has_feature!("sse2") {
+                backends.push(Backend::Sse2);
+            }
+            if has_feature!("avx2") {
+                backends.push(Backend::Avx2);
+            }
+        }
+
+        #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+        {
+            if has_feature!("avx512f") {
+                backends.push(Backend::Avx512);
+            }
+        }
+
+        backends
+    }
+
+    // How many blocks the backend computes at once.
+    fn parallel_blocks(self) -> usize {
+        match self {
+            Backend::Portable => 4,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse2 => 4,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => 8,
+            #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+            Backend::Avx512 => 16,
+        }
+    }
+}
+
+// XORs `data` with the key stream of `state`, starting at the block its
+// counter words point at.
+pub(crate) fn xor_keystream<const ROUNDS: usize>(state: &[u32; 16], data: &mut [u8]) {
+    xor_keystream_with::<ROUNDS>(Backend::detect(), state, data)
+}
+
+pub(crate) fn xor_keystream_with<const ROUNDS: usize>(
+    backend: Backend,
+    state: &[u32; 16],
+    data: &mut [u8],
+) {
+    // The SIMD backends only take whole groups of blocks; the rest goes
+    // through the portable code, which handles partial groups.
+    let chunk = backend.parallel_blocks() * 64;
+    let bulk = data.len() - data.len() % chunk;
+    let (head, tail) = data.split_at_mut(bulk);
+
+    match backend {
+        Backend::Portable => xor_blocks_portable::<ROUNDS>(state, head),
+        // Safety: `Backend::detect` and `Backend::available` only return
+        // backends the CPU supports.
+        #[cfg(target_arch = "x86_64")]
+        Backend::Sse2 => unsafe { sse2::xor_blocks::<ROUNDS>(state, head) },
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx2 => unsafe { avx2::xor_blocks::<ROUNDS>(state, head) },
+        #[cfg(all(target_arch = "x86_64", feature = "avx512"))]
+        Backend::Avx512 => unsafe { avx512::xor_blocks::<ROUNDS>(state, head) },
+    }
+
+    let mut state = *state;
+    add_counter(&mut state, (bulk / 64) as u64);
+    xor_blocks_portable::<ROUNDS>(&state, tail);
+    wipe(&mut state);
+}
+
+// Each thread takes at least this many blocks (64 KiB); below that, starting
+// a thread costs more than it saves.
+#[cfg(feature = "std")]
+const MIN_BLOCKS_PER_THREAD: usize = 1024;
+
+// Same as `xor_keystream`, but splits `data` at block boundaries into up to
+// `threads` pieces that are processed concurrently, each starting at its own
+// counter offset.  The output is identical to `xor_keystream`.  A `threads`
+// of 0 means one thread per CPU.
+#[cfg(feature = "std")]
+pub(crate) fn xor_keystream_parallel<const ROUNDS: usize>(
+    state: &[u32; 16],
+    data: &mut [u8],
+    threads: usize,
+) {
+    let threads = match threads {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let blocks = data.len().div_ceil(64);
+    let per_thread = blocks.div_ceil(threads).max(MIN_BLOCKS_PER_THREAD);
+    if per_thread >= blocks {
+        return xor_keystream::<ROUNDS>(state, data);
+    }
+
+    let backend = Backend::detect();
+    let piece = move |(i, chunk): (usize, &mut [u8])| {
+        let mut state = *state;
+        add_counter(&mut state, (i * per_thread) as u64);
+        xor_keystream_with::<ROUNDS>(backend, &state, chunk);
+        wipe(&mut state);
+    };
+
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+
+        let run = |data: &mut [u8]| {
+            data.par_chunks_mut(per_thread * 64)
+                .enumerate()
+                .for_each(piece)
+        };
+        // The global pool has a thread per CPU, so fewer threads need a pool
+        // of their own.  If one cannot be made, the work is done here alone.
+        if threads >= rayon::current_num_threads() {
+            run(data);
+        } else {
+            match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+                Ok(pool) => pool.install(|| run(data)),
+                Err(_) => xor_keystream_with::<ROUNDS>(backend, state, data),
+            }
+        }
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    {
+        std::thread::scope(|scope| {
+            let mut chunks = data.chunks_mut(per_thread * 64).enumerate();
+            let first = chunks.next();
+            for chunk in chunks {
+                scope.spawn(move || piece(chunk));
+            }
+            // The calling thread does its share instead of just waiting.
+            if let Some(chunk) = first {
+                piece(chunk);
+            }
+        });
+    }
+}
+
+fn xor_blocks_portable<const ROUNDS: usize>(state: &[u32; 16], data: &mut [u8]) {
+    let mut state = *state;
+    let mut key_stream = [0u8; 256];
+
+    // A short last chunk still computes four blocks and uses only the start.
+    for chunk in data.chunks_mut(256) {
+        chacha_blocks4::<ROUNDS>(&state, &mut key_stream);
+        for (b, k) in chunk.iter_mut().zip(key_stream.iter()) {
+            *b ^= k;
+        }
+        add_counter(&mut state, 4);
+    }
+
+    wipe(&mut state);
+    wipe(&mut key_stream);
+}
+
+// Reads the 64-bit block counter in words 12-13.
+fn counter(state: &[u32; 16]) -> u64 {
+    u64::from(state[12]) | u64::from(state[13]) << 32
+}
+
+fn add_counter(state: &mut [u32; 16], blocks: u64) {
+    let counter = counter(state).wrapping_add(blocks);
+    state[12] = counter as u32;
+    state[13] = (counter >> 32) as u32;
+}
+
+#[cfg(test)]
+use crate::chacha20::{init_state, init_state_legacy};
+#[cfg(test)]
+use crate::rng::ChaCha8Rng;
+#[cfg(test)]
+use alloc::{vec, vec::Vec};
+#[cfg(test)]
+use rand_core::{RngCore, SeedableRng};
+
+#[test]
+fn test_backends_rfc_vector() {
+    // 2.4.2.  Example and Test Vector for the ChaCha20 Cipher
+    let mut key = [0u8; 32];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    let nonce = [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let ciphertext = [
+        0x6e, 0x2e, 0x35, 0x9a, 0x25, 0x68, 0xf9, 0x80, 0x41, 0xba, 0x07, 0x28, 0xdd, 0x0d, 0x69,
+        0x81, 0xe9, 0x7e, 0x7a, 0xec, 0x1d, 0x43, 0x60, 0xc2, 0x0a, 0x27, 0xaf, 0xcc, 0xfd, 0x9f,
+        0xae, 0x0b, 0xf9, 0x1b, 0x65, 0xc5, 0x52, 0x47, 0x33, 0xab, 0x8f, 0x59, 0x3d, 0xab, 0xcd,
+        0x62, 0xb3, 0x57, 0x16, 0x39, 0xd6, 0x24, 0xe6, 0x51, 0x52, 0xab, 0x8f, 0x53, 0x0c, 0x35,
+        0x9f, 0x08, 0x61, 0xd8, 0x07, 0xca, 0x0d, 0xbf, 0x50, 0x0d, 0x6a, 0x61, 0x56, 0xa3, 0x8e,
+        0x08, 0x8a, 0x22, 0xb6, 0x5e, 0x52, 0xbc, 0x51, 0x4d, 0x16, 0xcc, 0xf8, 0x06, 0x81, 0x8c,
+        0xe9, 0x1a, 0xb7, 0x79, 0x37, 0x36, 0x5a, 0xf9, 0x0b, 0xbf, 0x74, 0xa3, 0x5b, 0xe6, 0xb4,
+        0x0b, 0x8e, 0xed, 0xf2, 0x78, 0x5e, 0x42, 0x87, 0x4d,
+    ];
+    let plaintext = b"Ladies and Gentlemen of the class of '99: \
+If I could offer you only one tip for the future, sunscreen would be it.";
+
+    for &backend in Backend::available().iter() {
+        // The vector itself is shorter than any group of blocks.
+        let mut data = plaintext.to_vec();
+        xor_keystream_with::<20>(backend, &init_state(&key, 1, &nonce), &mut data);
+        assert_eq!(data[..], ciphertext[..], "{:?}", backend);
+
+        // At the start of a 17-block message the vector goes through the
+        // bulk path instead, and the last block through the portable code.
+        // With AVX-512 the message is one 16-block pass and a tail.
+        let mut long = plaintext.to_vec();
+        long.resize(64 * 17, 0);
+        let mut expected = long.clone();
+        xor_keystream_with::<20>(backend, &init_state(&key, 1, &nonce), &mut long);
+        xor_keystream_with::<20>(
+            Backend::Portable,
+            &init_state(&key, 1, &nonce),
+            &mut expected,
+        );
+        assert_eq!(long[..plaintext.len()], ciphertext[..], "{:?}", backend);
+        assert_eq!(long, expected, "{:?}", backend);
+    }
+}
+
+#[cfg(test)]
+fn check_against_portable<const ROUNDS: usize>(rng: &mut ChaCha8Rng, state: &[u32; 16]) {
+    let len = (rng.next_u32() % 2048) as usize;
+    let mut data = vec![0u8; len];
+    rng.fill_bytes(&mut data);
+
+    let mut expected = data.clone();
+    xor_keystream_with::<ROUNDS>(Backend::Portable, state, &mut expected);
+
+    for &backend in Backend::available().iter() {
+        let mut actual = data.clone();
+        xor_keystream_with::<ROUNDS>(backend, state, &mut actual);
+        assert!(
+            actual == expected,
+            "{:?} with {} rounds, {} bytes",
+            backend,
+            ROUNDS,
+            len
+        );
+    }
+}
+
+#[test]
+fn test_backends_random_inputs() {
+    let mut rng = ChaCha8Rng::from_seed([0x5a; 32]);
+
+    for _ in 0..200 {
+        let mut key = [0u8; 32];
+        let mut nonce = [0u8; 12];
+        rng.fill_bytes(&mut key);
+        rng.fill_bytes(&mut nonce);
+        let state = init_state(&key, rng.next_u32() >> 8, &nonce);
+
+        check_against_portable::<8>(&mut rng, &state);
+        check_against_portable::<12>(&mut rng, &state);
+        check_against_portable::<20>(&mut rng, &state);
+    }
+}
+
+#[test]
+fn test_backends_counter_carry() {
+    // With the 64-bit counter layout, lanes that cross 2^32 must carry into
+    // word 13.
+    let mut rng = ChaCha8Rng::from_seed([0xa5; 32]);
+    let mut key = [0u8; 32];
+    rng.fill_bytes(&mut key);
+
+    for start in (1u64 << 32) - 9..(1 << 32) + 1 {
+        let state = init_state_legacy(&key, start, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        check_against_portable::<20>(&mut rng, &state);
+    }
+}
@@ -0,0 +1,123 @@
+// Eight blocks at a time with AVX2, laid out like the SSE2 backend: vector
+// `x[i]` holds word `i` of each of the eight blocks.
+
+use crate::wipe::wipe_with;
+use core::arch::x86_64::*;
+
+macro_rules! rotl {
+    ($x:expr, $n:literal) => {
+        _mm256_or_si256(_mm256_slli_epi32($x, $n), _mm256_srli_epi32($x, 32 - $n))
+    };
+}
+
+// Rotations by whole bytes are a single byte shuffle.
+macro_rules! rotl_bytes {
+    ($x:expr, $shuffle:expr) => {
+        _mm256_shuffle_epi8($x, $shuffle)
+    };
+}
+
+macro_rules! quarter_round {
+    ($x:ident, $rot16:ident, $rot8:ident, $a:literal, $b:literal, $c:literal, $d:literal) => {
+        $x[$a] = _mm256_add_epi32($x[$a], $x[$b]);
+        $x[$d] = rotl_bytes!(_mm256_xor_si256($x[$d], $x[$a]), $rot16);
+        $x[$c] = _mm256_add_epi32($x[$c], $x[$d]);
+        $x[$b] = rotl!(_mm256_xor_si256($x[$b], $x[$c]), 12);
+        $x[$a] = _mm256_add_epi32($x[$a], $x[$b]);
+        $x[$d] = rotl_bytes!(_mm256_xor_si256($x[$d], $x[$a]), $rot8);
+        $x[$c] = _mm256_add_epi32($x[$c], $x[$d]);
+        $x[$b] = rotl!(_mm256_xor_si256($x[$b], $x[$c]), 7);
+    };
+}
+
+// `data.len()` must be a multiple of 512.
+#[target_feature(enable = "avx2")]
+pub(super) unsafe fn xor_blocks<const ROUNDS: usize>(state: &[u32; 16], data: &mut [u8]) {
+    let rot16 = _mm256_set_epi8(
+        13, 12, 15, 14, 9, 8, 11, 10, 5, 4, 7, 6, 1, 0, 3, 2, 13, 12, 15, 14, 9, 8, 11, 10, 5, 4,
+        7, 6, 1, 0, 3, 2,
+    );
+    let rot8 = _mm256_set_epi8(
+        14, 13, 12, 15, 10, 9, 8, 11, 6, 5, 4, 7, 2, 1, 0, 3, 14, 13, 12, 15, 10, 9, 8, 11, 6, 5,
+        4, 7, 2, 1, 0, 3,
+    );
+    let mut counter = super::counter(state);
+
+    // The vectors hold the key and key stream, and are wiped at the end.
+    let mut init = [_mm256_setzero_si256(); 16];
+    let mut x = init;
+
+    for chunk in data.chunks_exact_mut(512) {
+        for (v, &word) in init.iter_mut().zip(state.iter()) {
+            *v = _mm256_set1_epi32(word as i32);
+        }
+
+        let mut low = [0i32; 8];
+        let mut high = [0i32; 8];
+        for lane in 0..8 {
+            let c = counter.wrapping_add(lane as u64);
+            low[lane] = c as i32;
+            high[lane] = (c >> 32) as i32;
+        }
+        init[12] = _mm256_loadu_si256(low.as_ptr() as *const __m256i);
+        init[13] = _mm256_loadu_si256(high.as_ptr() as *const __m256i);
+
+        x = init;
+        for _ in 0..ROUNDS / 2 {
+            quarter_round!(x, rot16, rot8, 0, 4, 8, 12);
+            quarter_round!(x, rot16, rot8, 1, 5, 9, 13);
+            quarter_round!(x, rot16, rot8, 2, 6, 10, 14);
+            quarter_round!(x, rot16, rot8, 3, 7, 11, 15);
+            quarter_round!(x, rot16, rot8, 0, 5, 10, 15);
+            quarter_round!(x, rot16, rot8, 1, 6, 11, 12);
+            quarter_round!(x, rot16, rot8, 2, 7, 8, 13);
+            quarter_round!(x, rot16, rot8, 3, 4, 9, 14);
+        }
+        for (v, i) in x.iter_mut().zip(init.iter()) {
+            *v = _mm256_add_epi32(*v, *i);
+        }
+
+        // Transposing four words within each 128-bit half gives 16 bytes of
+        // block k in the low half and of block k + 4 in the high half; two
+        // such groups are then joined into 32 bytes of one block.
+        for half in 0..2 {
+            let lo = transpose4(&x[8 * half..8 * half + 4]);
+            let hi = transpose4(&x[8 * half + 4..8 * half + 8]);
+
+            for k in 0..4 {
+                let first = _mm256_permute2x128_si256(lo[k], hi[k], 0x20);
+                let second = _mm256_permute2x128_si256(lo[k], hi[k], 0x31);
+                xor32(&mut chunk[64 * k + 32 * half..], first);
+                xor32(&mut chunk[64 * (k + 4) + 32 * half..], second);
+            }
+        }
+
+        counter = counter.wrapping_add(8);
+    }
+
+    wipe_with(&mut init, _mm256_setzero_si256());
+    wipe_with(&mut x, _mm256_setzero_si256());
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn transpose4(w: &[__m256i]) -> [__m256i; 4] {
+    let t0 = _mm256_unpacklo_epi32(w[0], w[1]);
+    let t1 = _mm256_unpacklo_epi32(w[2], w[3]);
+    let t2 = _mm256_unpackhi_epi32(w[0], w[1]);
+    let t3 = _mm256_unpackhi_epi32(w[2], w[3]);
+
+    [
+        _mm256_unpacklo_epi64(t0, t1),
+        _mm256_unpackhi_epi64(t0, t1),
+        _mm256_unpacklo_epi64(t2, t3),
+        _mm256_unpackhi_epi64(t2, t3),
+    ]
+}
+
+// XORs the first 32 bytes of `data` with `k`.
+#[target_feature(enable = "avx2")]
+unsafe fn xor32(data: &mut [u8], k: __m256i) {
+    assert!(data.len() >= 32);
+    let p = data.as_mut_ptr() as *mut __m256i;
+    _mm256_storeu_si256(p, _mm256_xor_si256(_mm256_loadu_si256(p), k));
+}
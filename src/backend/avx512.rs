@@ -0,0 +1,110 @@
+// Sixteen blocks at a time with AVX-512F, laid out like the SSE2 and AVX2
+// backends: vector `x[i]` holds word `i` of each of the sixteen blocks.
+// AVX-512 has a rotate instruction (`vprold`), so no shifts are needed.
+
+use crate::wipe::wipe_with;
+use core::arch::x86_64::*;
+
+macro_rules! quarter_round {
+    ($x:ident, $a:literal, $b:literal, $c:literal, $d:literal) => {
+        $x[$a] = _mm512_add_epi32($x[$a], $x[$b]);
+        $x[$d] = _mm512_rol_epi32::<16>(_mm512_xor_si512($x[$d], $x[$a]));
+        $x[$c] = _mm512_add_epi32($x[$c], $x[$d]);
+        $x[$b] = _mm512_rol_epi32::<12>(_mm512_xor_si512($x[$b], $x[$c]));
+        $x[$a] = _mm512_add_epi32($x[$a], $x[$b]);
+        $x[$d] = _mm512_rol_epi32::<8>(_mm512_xor_si512($x[$d], $x[$a]));
+        $x[$c] = _mm512_add_epi32($x[$c], $x[$d]);
+        $x[$b] = _mm512_rol_epi32::<7>(_mm512_xor_si512($x[$b], $x[$c]));
+    };
+}
+
+// `data.len()` must be a multiple of 1024.
+#[target_feature(enable = "avx512f")]
+pub(super) unsafe fn xor_blocks<const ROUNDS: usize>(state: &[u32; 16], data: &mut [u8]) {
+    let mut counter = super::counter(state);
+
+    // The vectors hold the key and key stream, and are wiped at the end.
+    let mut init = [_mm512_setzero_si512(); 16];
+    let mut x = init;
+
+    for chunk in data.chunks_exact_mut(1024) {
+        for (v, &word) in init.iter_mut().zip(state.iter()) {
+            *v = _mm512_set1_epi32(word as i32);
+        }
+
+        let mut low = [0i32; 16];
+        let mut high = [0i32; 16];
+        for lane in 0..16 {
+            let c = counter.wrapping_add(lane as u64);
+            low[lane] = c as i32;
+            high[lane] = (c >> 32) as i32;
+        }
+        init[12] = _mm512_loadu_si512(low.as_ptr() as *const __m512i);
+        init[13] = _mm512_loadu_si512(high.as_ptr() as *const __m512i);
+
+        x = init;
+        for _ in 0..ROUNDS / 2 {
+            quarter_round!(x, 0, 4, 8, 12);
+            quarter_round!(x, 1, 5, 9, 13);
+            quarter_round!(x, 2, 6, 10, 14);
+            quarter_round!(x, 3, 7, 11, 15);
+            quarter_round!(x, 0, 5, 10, 15);
+            quarter_round!(x, 1, 6, 11, 12);
+            quarter_round!(x, 2, 7, 8, 13);
+            quarter_round!(x, 3, 4, 9, 14);
+        }
+        for (v, i) in x.iter_mut().zip(init.iter()) {
+            *v = _mm512_add_epi32(*v, *i);
+        }
+
+        // Transposing four words within each 128-bit lane gives, for word
+        // group g, vectors whose lane l holds 16 bytes of block k + 4l.
+        let groups = [
+            transpose4(&x[0..4]),
+            transpose4(&x[4..8]),
+            transpose4(&x[8..12]),
+            transpose4(&x[12..16]),
+        ];
+
+        // Transposing the 128-bit lanes of the four groups then gathers all
+        // 64 bytes of each block.
+        for k in 0..4 {
+            let (a, b, c, d) = (groups[0][k], groups[1][k], groups[2][k], groups[3][k]);
+            let ab02 = _mm512_shuffle_i32x4(a, b, 0b10_00_10_00);
+            let ab13 = _mm512_shuffle_i32x4(a, b, 0b11_01_11_01);
+            let cd02 = _mm512_shuffle_i32x4(c, d, 0b10_00_10_00);
+            let cd13 = _mm512_shuffle_i32x4(c, d, 0b11_01_11_01);
+
+            let blocks = [
+                _mm512_shuffle_i32x4(ab02, cd02, 0b10_00_10_00),
+                _mm512_shuffle_i32x4(ab13, cd13, 0b10_00_10_00),
+                _mm512_shuffle_i32x4(ab02, cd02, 0b11_01_11_01),
+                _mm512_shuffle_i32x4(ab13, cd13, 0b11_01_11_01),
+            ];
+            for (l, block) in blocks.iter().enumerate() {
+                let p = chunk[64 * (k + 4 * l)..].as_mut_ptr() as *mut __m512i;
+                _mm512_storeu_si512(p, _mm512_xor_si512(_mm512_loadu_si512(p), *block));
+            }
+        }
+
+        counter = counter.wrapping_add(16);
+    }
+
+    wipe_with(&mut init, _mm512_setzero_si512());
+    wipe_with(&mut x, _mm512_setzero_si512());
+}
+
+#[target_feature(enable = "avx512f")]
+unsafe fn transpose4(w: &[__m512i]) -> [__m512i; 4] {
+    let t0 = _mm512_unpacklo_epi32(w[0], w[1]);
+    let t1 = _mm512_unpacklo_epi32(w[2], w[3]);
+    let t2 = _mm512_unpackhi_epi32(w[0], w[1]);
+    let t3 = _mm512_unpackhi_epi32(w[2], w[3]);
+
+    [
+        _mm512_unpacklo_epi64(t0, t1),
+        _mm512_unpackhi_epi64(t0, t1),
+        _mm512_unpacklo_epi64(t2, t3),
+        _mm512_unpackhi_epi64(t2, t3),
+    ]
+}
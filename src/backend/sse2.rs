@@ -0,0 +1,95 @@
+// Four blocks at a time with SSE2.  Vector `x[i]` holds word `i` of each of
+// the four blocks, so a quarter round on the vectors is a quarter round on
+// every block.
+
+use crate::wipe::wipe_with;
+use core::arch::x86_64::*;
+
+macro_rules! rotl {
+    ($x:expr, $n:literal) => {
+        _mm_or_si128(_mm_slli_epi32($x, $n), _mm_srli_epi32($x, 32 - $n))
+    };
+}
+
+macro_rules! quarter_round {
+    ($x:ident, $a:literal, $b:literal, $c:literal, $d:literal) => {
+        $x[$a] = _mm_add_epi32($x[$a], $x[$b]);
+        $x[$d] = rotl!(_mm_xor_si128($x[$d], $x[$a]), 16);
+        $x[$c] = _mm_add_epi32($x[$c], $x[$d]);
+        $x[$b] = rotl!(_mm_xor_si128($x[$b], $x[$c]), 12);
+        $x[$a] = _mm_add_epi32($x[$a], $x[$b]);
+        $x[$d] = rotl!(_mm_xor_si128($x[$d], $x[$a]), 8);
+        $x[$c] = _mm_add_epi32($x[$c], $x[$d]);
+        $x[$b] = rotl!(_mm_xor_si128($x[$b], $x[$c]), 7);
+    };
+}
+
+// `data.len()` must be a multiple of 256.
+#[target_feature(enable = "sse2")]
+pub(super) unsafe fn xor_blocks<const ROUNDS: usize>(state: &[u32; 16], data: &mut [u8]) {
+    let mut counter = super::counter(state);
+
+    // The vectors hold the key and key stream, and are wiped at the end.
+    let mut init = [_mm_setzero_si128(); 16];
+    let mut x = init;
+
+    for chunk in data.chunks_exact_mut(256) {
+        for (v, &word) in init.iter_mut().zip(state.iter()) {
+            *v = _mm_set1_epi32(word as i32);
+        }
+
+        let c: [u64; 4] = [
+            counter,
+            counter.wrapping_add(1),
+            counter.wrapping_add(2),
+            counter.wrapping_add(3),
+        ];
+        init[12] = _mm_set_epi32(c[3] as i32, c[2] as i32, c[1] as i32, c[0] as i32);
+        init[13] = _mm_set_epi32(
+            (c[3] >> 32) as i32,
+            (c[2] >> 32) as i32,
+            (c[1] >> 32) as i32,
+            (c[0] >> 32) as i32,
+        );
+
+        x = init;
+        for _ in 0..ROUNDS / 2 {
+            quarter_round!(x, 0, 4, 8, 12);
+            quarter_round!(x, 1, 5, 9, 13);
+            quarter_round!(x, 2, 6, 10, 14);
+            quarter_round!(x, 3, 7, 11, 15);
+            quarter_round!(x, 0, 5, 10, 15);
+            quarter_round!(x, 1, 6, 11, 12);
+            quarter_round!(x, 2, 7, 8, 13);
+            quarter_round!(x, 3, 4, 9, 14);
+        }
+        for (v, i) in x.iter_mut().zip(init.iter()) {
+            *v = _mm_add_epi32(*v, *i);
+        }
+
+        // Transposing each group of four words gives 16 bytes of each block.
+        for group in 0..4 {
+            let w = &x[4 * group..4 * group + 4];
+            let t0 = _mm_unpacklo_epi32(w[0], w[1]);
+            let t1 = _mm_unpacklo_epi32(w[2], w[3]);
+            let t2 = _mm_unpackhi_epi32(w[0], w[1]);
+            let t3 = _mm_unpackhi_epi32(w[2], w[3]);
+            let blocks = [
+                _mm_unpacklo_epi64(t0, t1),
+                _mm_unpackhi_epi64(t0, t1),
+                _mm_unpacklo_epi64(t2, t3),
+                _mm_unpackhi_epi64(t2, t3),
+            ];
+
+            for (block, k) in blocks.iter().enumerate() {
+                let p = chunk[64 * block + 16 * group..].as_mut_ptr() as *mut __m128i;
+                _mm_storeu_si128(p, _mm_xor_si128(_mm_loadu_si128(p), *k));
+            }
+        }
+
+        counter = counter.wrapping_add(4);
+    }
+
+    wipe_with(&mut init, _mm_setzero_si128());
+    wipe_with(&mut x, _mm_setzero_si128());
+}
@@ -1,4 +1,12 @@
-use std::mem;
+use crate::backend::xor_keystream;
+#[cfg(feature = "std")]
+use crate::backend::xor_keystream_parallel;
+use crate::error::ChaChaError;
+use crate::wipe::wipe;
+#[cfg(all(test, feature = "alloc"))]
+use alloc::vec;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 
 // 2.1.  The ChaCha Quarter Round
 
@@ -10,6 +18,7 @@ use std::mem;
 //    2.  c += d; b ^= c; b <<<= 12;
 //    3.  a += b; d ^= a; d <<<= 8;
 //    4.  c += d; b ^= c; b <<<= 7;
+#[cfg(test)]
 fn quarter_round(mut a: u32, mut b: u32, mut c: u32, mut d: u32) -> (u32, u32, u32, u32) {
     a = a.overflowing_add(b).0;
     d ^= a;
@@ -93,6 +102,7 @@ fn test_quarter_round() {
 //       8  *c  10  11
 //      12  *d  14  15
 
+#[cfg(all(test, feature = "alloc"))]
 fn apply_quarter_round(x: usize, y: usize, z: usize, w: usize, words_16: Vec<u32>) -> Vec<u32> {
     let old_a = words_16[x];
     let old_b = words_16[y];
@@ -137,6 +147,7 @@ fn apply_quarter_round(x: usize, y: usize, z: usize, w: usize, words_16: Vec<u32
 //    Note that only the numbers in positions 2, 7, 8, and 13 changed.
 
 #[test]
+#[cfg(feature = "alloc")]
 fn test_apply_quarter_round() {
     let input: Vec<u32> = vec![
         0x879531e0, 0xc5ecf37d, 0x516461b1, 0xc9a62f8a, 0x44c20ef3, 0x3390af7f, 0xd9fc690b,
@@ -156,6 +167,7 @@ fn test_apply_quarter_round() {
 }
 
 #[test]
+#[cfg(feature = "alloc")]
 fn test_apply_quarter_round_2() {
     let mut input: Vec<u32> = vec![
         0x879531e0, 0xc5ecf37d, 0x516461b1, 0xc9a62f8a, 0x44c20ef3, 0x3390af7f, 0xd9fc690b,
@@ -175,10 +187,22 @@ fn test_apply_quarter_round_2() {
     assert_eq!(output, input);
 }
 
-fn setup_key(key: Vec<u8>, counter: u32, nonce: Vec<u8>) -> Vec<u32> {
+#[cfg(all(test, feature = "alloc"))]
+fn setup_key(key: &[u8; 32], counter: u32, nonce: &[u8; 12]) -> Vec<u32> {
+    let mut state = init_state(key, counter, nonce);
+    let words = state.to_vec();
+    wipe(&mut state);
+
+    words
+}
+
+/// Lays out the ChaCha20 state of RFC 8439 section 2.3 that
+/// `chacha20_block` takes: four constants, the key, the block counter and
+/// the nonce, read as little-endian words.
+pub fn init_state(key: &[u8; 32], counter: u32, nonce: &[u8; 12]) -> [u32; 16] {
     // The ChaCha20 state is initialized as follows:
 
-    let mut state: Vec<u32> = vec![0; 16];
+    let mut state = [0u32; 16];
 
     // o  The first four words (0-3) are constants: 0x61707865, 0x3320646e,
     //    0x79622d32, 0x6b206574.
@@ -189,18 +213,8 @@ fn setup_key(key: Vec<u8>, counter: u32, nonce: Vec<u8>) -> Vec<u32> {
 
     // o  The next eight words (4-11) are taken from the 256-bit key by
     //    reading the bytes in little-endian order, in 4-byte chunks.
-    for i in 0..8 {
-        let idx_state = i + 4;
-        let idx_key = 4 * i;
-
-        unsafe {
-            state[idx_state] = mem::transmute::<[u8; 4], u32>([
-                key[idx_key],
-                key[idx_key + 1],
-                key[idx_key + 2],
-                key[idx_key + 3],
-            ]);
-        }
+    for (word, bytes) in state[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *word = load_le(bytes);
     }
 
     // o  Word 12 is a block counter.  Since each block is 64-byte, a 32-bit
@@ -211,24 +225,20 @@ fn setup_key(key: Vec<u8>, counter: u32, nonce: Vec<u8>) -> Vec<u32> {
     //    key.  The 13th word is the first 32 bits of the input nonce taken
     //    as a little-endian integer, while the 15th word is the last 32
     //    bits.
-    for i in 0..3 {
-        let idx_state = 13 + i;
-        let idx_nonce = 4 * i;
-
-        unsafe {
-            state[idx_state] = mem::transmute::<[u8; 4], u32>([
-                nonce[idx_nonce],
-                nonce[idx_nonce + 1],
-                nonce[idx_nonce + 2],
-                nonce[idx_nonce + 3],
-            ]);
-        }
+    for (word, bytes) in state[13..].iter_mut().zip(nonce.chunks_exact(4)) {
+        *word = load_le(bytes);
     }
 
     state
 }
 
+// Reads four bytes as a little-endian word, whatever the host byte order.
+fn load_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
 #[test]
+#[cfg(feature = "alloc")]
 fn test_setup_key() {
     // o  Key = 00:01:02:03:04:05:06:07:08:09:0a:0b:0c:0d:0e:0f:10:11:12:13:
     //    14:15:16:17:18:19:1a:1b:1c:1d:1e:1f.  The key is a sequence of
@@ -236,17 +246,17 @@ fn test_setup_key() {
     //    ChaCha state.
     // o  Nonce = (00:00:00:09:00:00:00:4a:00:00:00:00)
     // o  Block Count = 1.
-    let key: Vec<u8> = vec![
+    let key: [u8; 32] = [
         0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
         0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
         0x1e, 0x1f,
     ];
-    let nonce: Vec<u8> = vec![
+    let nonce: [u8; 12] = [
         0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
     ];
     let block_count = 1;
 
-    let actual = setup_key(key, block_count, nonce);
+    let actual = setup_key(&key, block_count, &nonce);
 
     // ChaCha state with the key setup.
 
@@ -263,6 +273,60 @@ fn test_setup_key() {
     assert_eq!(actual, expected);
 }
 
+#[test]
+fn test_byte_order() {
+    // The RFC 8439 section 2.3.2 state, built from bytes and serialized back
+    // to bytes.  A big-endian host reading or writing words in its native
+    // order would see every word byte-swapped; swapping the reference words
+    // simulates that, and the results must still match the RFC.
+    let mut key = [0u8; 32];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    let nonce: [u8; 12] = [
+        0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let expected_state: [u32; 16] = [
+        0x61707865, 0x3320646e, 0x79622d32, 0x6b206574, 0x03020100, 0x07060504, 0x0b0a0908,
+        0x0f0e0d0c, 0x13121110, 0x17161514, 0x1b1a1918, 0x1f1e1d1c, 0x00000001, 0x09000000,
+        0x4a000000, 0x00000000,
+    ];
+
+    let state = init_state(&key, 1, &nonce);
+    assert_eq!(state, expected_state);
+
+    let input = key.chunks_exact(4).chain(nonce.chunks_exact(4));
+    let words = state[4..12].iter().chain(state[13..].iter());
+    for (bytes, &word) in input.zip(words) {
+        let big_endian = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        assert_eq!(big_endian.swap_bytes(), word);
+    }
+
+    // 000  10 f1 e7 e4 d1 3b 59 15 50 0f dd 1f a3 20 71 c4  .....;Y.P.... q.
+    let output = chacha20_block(&state);
+    assert_eq!(
+        output[..4],
+        [0xe4e7f110, 0x15593bd1, 0x1fdd0f50, 0xc47120a3]
+    );
+
+    let mut serialized = [0u8; 64];
+    serialize_block(&output, &mut serialized);
+    assert_eq!(
+        serialized[..16],
+        [
+            0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15, 0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20,
+            0x71, 0xc4
+        ]
+    );
+    for (bytes, word) in serialized.chunks_exact(4).zip(output.iter()) {
+        assert_eq!(bytes, word.swap_bytes().to_be_bytes());
+    }
+
+    let mut interleaved = [0u8; 256];
+    chacha_blocks4::<20>(&state, &mut interleaved);
+    assert_eq!(interleaved[..64], serialized[..]);
+}
+
 // 2.3.1.  The ChaCha20 Block Function in Pseudocode
 
 //    Note: This section and a few others contain pseudocode for the
@@ -292,13 +356,42 @@ fn test_setup_key() {
 //          return serialize(state)
 //          end
 
-fn block_function(key: Vec<u8>, counter: u32, nonce: Vec<u8>) -> Vec<u32> {
-    // The ChaCha20 state is initialized as follows:
+#[cfg(all(test, feature = "alloc"))]
+fn block_function(key: &[u8; 32], counter: u32, nonce: &[u8; 12]) -> Vec<u32> {
+    let mut state = init_state(key, counter, nonce);
+    let mut block = chacha20_block(&state);
+    let words = block.to_vec();
+    wipe(&mut state);
+    wipe(&mut block);
+
+    words
+}
+
+/// The ChaCha20 block function of RFC 8439 on a state made by
+/// `init_state`.  Returns the state after the rounds, added to the input
+/// state, for `serialize_block` to turn into key stream.
+pub fn chacha20_block(state: &[u32; 16]) -> [u32; 16] {
+    chacha_block::<20>(state)
+}
 
-    let mut state = setup_key(key, counter, nonce);
+// The block function with a configurable number of rounds, for the reduced-
+// round variants ChaCha8 and ChaCha12.  `ROUNDS` counts single rounds, so
+// it must be even; inner_block runs ROUNDS / 2 times.
+pub fn chacha_block<const ROUNDS: usize>(state: &[u32; 16]) -> [u32; 16] {
+    let mut x = *state;
+    chacha_rounds::<ROUNDS>(&mut x);
 
-    let mut x = state.clone();
-    for _ in 1..=10 {
+    for i in 0..16 {
+        x[i] = state[i].overflowing_add(x[i]).0;
+    }
+
+    x
+}
+
+// The rounds (ROUNDS / 2 iterations of inner_block) without the final
+// addition of the input state, shared by the block function and HChaCha20.
+fn chacha_rounds<const ROUNDS: usize>(x: &mut [u32; 16]) {
+    for _ in 1..=ROUNDS / 2 {
         macro_quarter_round!(x[0], x[4], x[8], x[12]);
         macro_quarter_round!(x[1], x[5], x[9], x[13]);
         macro_quarter_round!(x[2], x[6], x[10], x[14]);
@@ -308,30 +401,97 @@ fn block_function(key: Vec<u8>, counter: u32, nonce: Vec<u8>) -> Vec<u32> {
         macro_quarter_round!(x[2], x[7], x[8], x[13]);
         macro_quarter_round!(x[3], x[4], x[9], x[14]);
     }
+}
 
-    for i in 0..16 {
-        state[i] = state[i].overflowing_add(x[i]).0;
+// Four consecutive blocks at once, starting at the counter in `state`, with
+// the blocks serialized into `out`.  `x[i][lane]` is word `i` of block
+// `lane`, so every step of a quarter round is the same operation on four
+// adjacent words, which the compiler can turn into vector instructions
+// without any target-specific code.  Like the backends, this treats words
+// 12-13 as a 64-bit block counter.
+pub fn chacha_blocks4<const ROUNDS: usize>(state: &[u32; 16], out: &mut [u8; 256]) {
+    let mut init = [[0u32; 4]; 16];
+    for (lanes, &word) in init.iter_mut().zip(state.iter()) {
+        *lanes = [word; 4];
+    }
+    let counter = u64::from(state[12]) | u64::from(state[13]) << 32;
+    let counters = [0, 1, 2, 3].map(|lane| counter.wrapping_add(lane));
+    init[12] = counters.map(|c| c as u32);
+    init[13] = counters.map(|c| (c >> 32) as u32);
+
+    let mut x = init;
+    for _ in 0..ROUNDS / 2 {
+        quarter_round4(&mut x, 0, 4, 8, 12);
+        quarter_round4(&mut x, 1, 5, 9, 13);
+        quarter_round4(&mut x, 2, 6, 10, 14);
+        quarter_round4(&mut x, 3, 7, 11, 15);
+        quarter_round4(&mut x, 0, 5, 10, 15);
+        quarter_round4(&mut x, 1, 6, 11, 12);
+        quarter_round4(&mut x, 2, 7, 8, 13);
+        quarter_round4(&mut x, 3, 4, 9, 14);
     }
 
-    state
+    for (block, lane) in out.chunks_exact_mut(64).zip(0..4) {
+        for (bytes, (w, i)) in block.chunks_exact_mut(4).zip(x.iter().zip(init.iter())) {
+            bytes.copy_from_slice(&w[lane].wrapping_add(i[lane]).to_le_bytes());
+        }
+    }
+
+    wipe(&mut init);
+    wipe(&mut x);
 }
 
+#[inline(always)]
+fn quarter_round4(x: &mut [[u32; 4]; 16], a: usize, b: usize, c: usize, d: usize) {
+    let (mut va, mut vb, mut vc, mut vd) = (x[a], x[b], x[c], x[d]);
+    let lanes = va
+        .iter_mut()
+        .zip(vb.iter_mut())
+        .zip(vc.iter_mut())
+        .zip(vd.iter_mut());
+    for (((a, b), c), d) in lanes {
+        macro_quarter_round!(*a, *b, *c, *d);
+    }
+    x[a] = va;
+    x[b] = vb;
+    x[c] = vc;
+    x[d] = vd;
+}
+
+#[cfg(all(test, feature = "alloc"))]
 fn serialized(arr32: Vec<u32>) -> Vec<u8> {
     let mut serialized: Vec<u8> = vec![0; arr32.len() * 4];
     for i in 0..16 {
-        unsafe {
-            let arr8 = mem::transmute::<u32, [u8; 4]>(arr32[i]);
-            serialized[i * 4] = arr8[0];
-            serialized[i * 4 + 1] = arr8[1];
-            serialized[i * 4 + 2] = arr8[2];
-            serialized[i * 4 + 3] = arr8[3];
-        }
+        let arr8 = arr32[i].to_le_bytes();
+        serialized[i * 4] = arr8[0];
+        serialized[i * 4 + 1] = arr8[1];
+        serialized[i * 4 + 2] = arr8[2];
+        serialized[i * 4 + 3] = arr8[3];
     }
 
     serialized
 }
 
+/// Writes a block returned by `chacha20_block` to `out` as 64 bytes of key
+/// stream.
+pub fn serialize_block(arr32: &[u32; 16], out: &mut [u8; 64]) {
+    //    ChaCha20 then serializes the state by sequencing the words one-by-one
+    //    in little-endian order.
+    for (word, bytes) in arr32.iter().zip(out.chunks_exact_mut(4)) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+}
+
+// One serialized block of key stream, without leaving the words behind on
+// the stack.
+pub(crate) fn keystream_block<const ROUNDS: usize>(state: &[u32; 16], out: &mut [u8; 64]) {
+    let mut words = chacha_block::<ROUNDS>(state);
+    serialize_block(&words, out);
+    wipe(&mut words);
+}
+
 #[test]
+#[cfg(feature = "alloc")]
 fn test_block_function() {
     // o  Key = 00:01:02:03:04:05:06:07:08:09:0a:0b:0c:0d:0e:0f:10:11:12:13:
     //    14:15:16:17:18:19:1a:1b:1c:1d:1e:1f.  The key is a sequence of
@@ -339,17 +499,17 @@ fn test_block_function() {
     //    ChaCha state.
     // o  Nonce = (00:00:00:09:00:00:00:4a:00:00:00:00)
     // o  Block Count = 1.
-    let key: Vec<u8> = vec![
+    let key: [u8; 32] = [
         0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
         0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
         0x1e, 0x1f,
     ];
-    let nonce: Vec<u8> = vec![
+    let nonce: [u8; 12] = [
         0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
     ];
     let block_count = 1;
 
-    let actual = block_function(key, block_count, nonce);
+    let actual = block_function(&key, block_count, &nonce);
 
     // ChaCha state after 20 rounds
 
@@ -390,50 +550,158 @@ fn test_block_function() {
     assert_eq!(serialized(actual), expected);
 }
 
-fn chacha20_encrypt(key: Vec<u8>, counter: u32, nonce: Vec<u8>, plaintext: Vec<u8>) -> Vec<u8> {
-    let mut encrypted_message = vec![0; plaintext.len()];
+#[test]
+fn test_chacha_blocks4() {
+    // Each lane must match the single-block function at its own counter,
+    // including lanes whose counter carries into word 13.
+    let key: [u8; 32] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
+        0x1e, 0x1f,
+    ];
+    let nonce: [u8; 12] = [
+        0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let states = [
+        init_state(&key, 1, &nonce),
+        init_state_legacy(&key, (1 << 32) - 2, &[1, 2, 3, 4, 5, 6, 7, 8]),
+    ];
+
+    for state in states.iter() {
+        let mut out = [0u8; 256];
+        chacha_blocks4::<20>(state, &mut out);
 
-    for j in 0..(plaintext.len() / 64) {
-        let key_stream = serialized(block_function(
-            key.clone(),
-            counter + j as u32,
-            nonce.clone(),
-        ));
-        let block = &plaintext[j * 64..=(j * 64 + 63)];
+        let mut state = *state;
+        for block in out.chunks_exact(64) {
+            let mut expected = [0u8; 64];
+            serialize_block(&chacha_block::<20>(&state), &mut expected);
+            assert_eq!(block[..], expected[..]);
 
-        for k in 0..64 {
-            encrypted_message[j * 64 + k] = block[k] ^ key_stream[k];
+            let counter = (u64::from(state[12]) | u64::from(state[13]) << 32) + 1;
+            state[12] = counter as u32;
+            state[13] = (counter >> 32) as u32;
         }
     }
-    if plaintext.len() % 64 != 1 {
-        let j = plaintext.len() / 64;
-        let key_stream = serialized(block_function(
-            key.clone(),
-            counter + j as u32,
-            nonce.clone(),
-        ));
-        let block = &plaintext[j * 64..plaintext.len()];
-
-        for k in 0..plaintext.len() % 64 {
-            encrypted_message[j * 64 + k] = block[k] ^ key_stream[k];
-        }
+
+    // Lane 0 is the RFC 8439 test vector block.
+    let mut out = [0u8; 256];
+    #[cfg(feature = "alloc")]
+    {
+        chacha_blocks4::<20>(&init_state(&key, 1, &nonce), &mut out);
+        assert_eq!(out[..64], serialized(block_function(&key, 1, &nonce))[..]);
     }
 
-    encrypted_message
+    // The reduced-round variants.
+    let state = init_state(&key, 7, &nonce);
+    let mut expected = [0u8; 64];
+    chacha_blocks4::<8>(&state, &mut out);
+    serialize_block(&chacha_block::<8>(&state), &mut expected);
+    assert_eq!(out[..64], expected[..]);
+    chacha_blocks4::<12>(&state, &mut out);
+    serialize_block(&chacha_block::<12>(&state), &mut expected);
+    assert_eq!(out[..64], expected[..]);
+}
+
+/// Encrypts or decrypts `plaintext` with ChaCha20, starting at block
+/// `counter`.
+///
+/// Fails with `CounterExhausted` if the 32-bit block counter would wrap.
+#[cfg(feature = "alloc")]
+pub fn chacha20_encrypt(
+    key: &[u8; 32],
+    counter: u32,
+    nonce: &[u8; 12],
+    plaintext: Vec<u8>,
+) -> Result<Vec<u8>, ChaChaError> {
+    let mut encrypted_message = plaintext;
+    chacha20_encrypt_in_place(key, counter, nonce, &mut encrypted_message)?;
+
+    Ok(encrypted_message)
+}
+
+// Encrypts `data` in place without any heap allocation, using the fastest
+// backend the CPU supports.
+pub fn chacha20_encrypt_in_place(
+    key: &[u8; 32],
+    counter: u32,
+    nonce: &[u8; 12],
+    data: &mut [u8],
+) -> Result<(), ChaChaError> {
+    chacha_encrypt_in_place::<20>(key, counter, nonce, data)
+}
+
+// Checks that `len` bytes of key stream starting at block `counter` fit in a
+// counter that has `limit` values.
+pub(crate) fn check_counter(counter: u128, len: usize, limit: u128) -> Result<(), ChaChaError> {
+    if (len as u128).div_ceil(64) > limit - counter {
+        return Err(ChaChaError::CounterExhausted);
+    }
+
+    Ok(())
+}
+
+// Same as `chacha20_encrypt_in_place` with a configurable number of rounds.
+//
+// Nothing is encrypted if the message needs blocks past counter 2^32 - 1:
+// the counter would wrap around and reuse key stream.
+pub fn chacha_encrypt_in_place<const ROUNDS: usize>(
+    key: &[u8; 32],
+    counter: u32,
+    nonce: &[u8; 12],
+    data: &mut [u8],
+) -> Result<(), ChaChaError> {
+    check_counter(u128::from(counter), data.len(), 1 << 32)?;
+
+    let mut state = init_state(key, counter, nonce);
+    xor_keystream::<ROUNDS>(&state, data);
+    wipe(&mut state);
+    Ok(())
+}
+
+// Same as `chacha20_encrypt_in_place`, but large messages are split at block
+// boundaries across up to `threads` threads (0 for one per CPU).  The output
+// is the same as the serial version.
+#[cfg(feature = "std")]
+pub fn chacha20_encrypt_parallel(
+    key: &[u8; 32],
+    counter: u32,
+    nonce: &[u8; 12],
+    data: &mut [u8],
+    threads: usize,
+) -> Result<(), ChaChaError> {
+    chacha_encrypt_parallel::<20>(key, counter, nonce, data, threads)
+}
+
+// Same as `chacha20_encrypt_parallel` with a configurable number of rounds.
+#[cfg(feature = "std")]
+pub fn chacha_encrypt_parallel<const ROUNDS: usize>(
+    key: &[u8; 32],
+    counter: u32,
+    nonce: &[u8; 12],
+    data: &mut [u8],
+    threads: usize,
+) -> Result<(), ChaChaError> {
+    check_counter(u128::from(counter), data.len(), 1 << 32)?;
+
+    let mut state = init_state(key, counter, nonce);
+    xor_keystream_parallel::<ROUNDS>(&state, data, threads);
+    wipe(&mut state);
+    Ok(())
 }
 
 #[test]
+#[cfg(feature = "alloc")]
 fn test_chacha20_encrypt() {
     // o  Key = 00:01:02:03:04:05:06:07:08:09:0a:0b:0c:0d:0e:0f:10:11:12:13:
     //    14:15:16:17:18:19:1a:1b:1c:1d:1e:1f.
     // o  Nonce = (00:00:00:00:00:00:00:4a:00:00:00:00)
     // o  Initial block Count = 1.
-    let key: Vec<u8> = vec![
+    let key: [u8; 32] = [
         0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
         0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
         0x1e, 0x1f,
     ];
-    let nonce: Vec<u8> = vec![
+    let nonce: [u8; 12] = [
         0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
     ];
     let initial_block_count = 1;
@@ -478,22 +746,102 @@ fn test_chacha20_encrypt() {
         0x0b, 0x8e, 0xed, 0xf2, 0x78, 0x5e, 0x42, 0x87, 0x4d,
     ];
 
-    let actual = chacha20_encrypt(key, initial_block_count, nonce, plain_test);
+    let actual = chacha20_encrypt(&key, initial_block_count, &nonce, plain_test).unwrap();
 
     assert_eq!(cipher_text, actual);
 }
 
 #[test]
+#[cfg(feature = "alloc")]
+fn test_chacha20_encrypt_partial_block() {
+    // Encrypting twice gives back the input whatever its length, including
+    // one byte past a whole number of blocks.
+    let mut key = [0u8; 32];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    let nonce = [0u8; 12];
+    for &len in [1, 63, 64, 65, 128, 129].iter() {
+        let plaintext: Vec<u8> = (0..len).map(|i| i as u8 | 1).collect();
+        let ciphertext = chacha20_encrypt(&key, 1, &nonce, plaintext.clone()).unwrap();
+        let decrypted = chacha20_encrypt(&key, 1, &nonce, ciphertext).unwrap();
+        assert_eq!(decrypted, plaintext, "{}", len);
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_counter_exhausted() {
+    let key = [0u8; 32];
+    let nonce = [0u8; 12];
+
+    // The last block may be used, but not wrapped past.
+    let last = chacha20_encrypt(&key, u32::MAX, &nonce, vec![0; 64]).unwrap();
+    let first = chacha20_encrypt(&key, 0, &nonce, vec![0; 64]).unwrap();
+    assert_ne!(last, first);
+
+    assert_eq!(
+        chacha20_encrypt(&key, u32::MAX, &nonce, vec![0; 65]),
+        Err(ChaChaError::CounterExhausted)
+    );
+    assert_eq!(
+        chacha20_encrypt(&key, u32::MAX - 1, &nonce, vec![0; 129]),
+        Err(ChaChaError::CounterExhausted)
+    );
+
+    let mut data = [0x11; 65];
+    assert_eq!(
+        chacha20_encrypt_in_place(&[0; 32], u32::MAX, &[0; 12], &mut data),
+        Err(ChaChaError::CounterExhausted)
+    );
+    assert_eq!(data, [0x11; 65]);
+
+    assert_eq!(
+        chacha_legacy_encrypt_in_place::<20>(&[0; 32], u64::MAX, &[0; 8], &mut data),
+        Err(ChaChaError::CounterExhausted)
+    );
+    assert!(
+        chacha_legacy_encrypt_in_place::<20>(&[0; 32], u64::MAX, &[0; 8], &mut data[..64]).is_ok()
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_chacha20_encrypt_parallel() {
+    let key = [0x42; 32];
+    let nonce = [7; 12];
+
+    // Lengths that give a single piece, pieces ending mid-block, and more
+    // threads than pieces.
+    for &len in [0, 100, 64 * 1024 + 1, 1 << 20, (1 << 20) + 37].iter() {
+        let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        let expected = chacha20_encrypt(&key, 5, &nonce, data.clone()).unwrap();
+
+        for &threads in [0, 1, 2, 3, 7, 64].iter() {
+            let mut actual = data.clone();
+            chacha20_encrypt_parallel(&key, 5, &nonce, &mut actual, threads).unwrap();
+            assert!(actual == expected, "{} bytes, {} threads", len, threads);
+        }
+    }
+
+    let mut data = vec![0x11; 1 << 20];
+    assert_eq!(
+        chacha20_encrypt_parallel(&key, u32::MAX - 100, &nonce, &mut data, 4),
+        Err(ChaChaError::CounterExhausted)
+    );
+    assert!(data.iter().all(|&b| b == 0x11));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
 fn test_generate_rng() {
-    let seed: Vec<u8> = vec![
+    let seed: [u8; 32] = [
         0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
         0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
         0x1e, 0x1f,
     ];
 
-    let nonce: Vec<u8> = vec![
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    ];
+    let nonce = [0u8; 12];
 
     let expected: Vec<u32> = vec![
         2100034873, 1780073945, 1996733837, 1229642936, 1876440458, 3429555900, 1283312818,
@@ -504,13 +852,138 @@ fn test_generate_rng() {
         997363241, 914301792, 3082742343, 815587571, 3806560462,
     ];
 
-    let r = block_function(seed.clone(), 0, nonce.clone());
+    let r = block_function(&seed, 0, &nonce);
     for i in 0..16 {
         assert_eq!(expected[i], r[i]);
     }
 
-    let r = block_function(seed.clone(), 1, nonce.clone());
+    let r = block_function(&seed, 1, &nonce);
     for i in 0..16 {
         assert_eq!(expected[i + 16], r[i]);
     }
 }
+
+// The original ChaCha layout from Bernstein's reference implementation,
+// which RFC 8439 changed: words 12-13 are a 64-bit block counter and words
+// 14-15 a 64-bit nonce, both little-endian.  A 64-bit counter makes the key
+// stream 2^70 bytes long instead of 256 gigabytes.
+pub fn init_state_legacy(key: &[u8; 32], counter: u64, nonce: &[u8; 8]) -> [u32; 16] {
+    let mut iv = [0u8; 12];
+    iv[4..].copy_from_slice(nonce);
+
+    // Apart from the counter spilling into word 13, this is the RFC layout.
+    let mut state = init_state(key, counter as u32, &iv);
+    state[13] = (counter >> 32) as u32;
+    state
+}
+
+// Same as `chacha_encrypt_in_place` with the original 64-bit counter layout.
+pub fn chacha_legacy_encrypt_in_place<const ROUNDS: usize>(
+    key: &[u8; 32],
+    counter: u64,
+    nonce: &[u8; 8],
+    data: &mut [u8],
+) -> Result<(), ChaChaError> {
+    check_counter(u128::from(counter), data.len(), 1 << 64)?;
+
+    let mut state = init_state_legacy(key, counter, nonce);
+    xor_keystream::<ROUNDS>(&state, data);
+    wipe(&mut state);
+    Ok(())
+}
+
+#[test]
+fn test_init_state_legacy() {
+    let key: [u8; 32] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
+        0x1e, 0x1f,
+    ];
+    let nonce: [u8; 8] = [0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x09];
+
+    let actual = init_state_legacy(&key, 0x0000_0002_0000_0001, &nonce);
+
+    // 61707865  3320646e  79622d32  6b206574
+    // 03020100  07060504  0b0a0908  0f0e0d0c
+    // 13121110  17161514  1b1a1918  1f1e1d1c
+    // 00000001  00000002  4a000000  09000000
+    let expected = [
+        0x61707865, 0x3320646e, 0x79622d32, 0x6b206574, 0x03020100, 0x07060504, 0x0b0a0908,
+        0x0f0e0d0c, 0x13121110, 0x17161514, 0x1b1a1918, 0x1f1e1d1c, 0x00000001, 0x00000002,
+        0x4a000000, 0x09000000,
+    ];
+
+    assert_eq!(actual, expected);
+}
+
+// draft-irtf-cfrg-xchacha, 2.2.  HChaCha20
+
+//    HChaCha20 is an intermediary step towards XChaCha20 based on the
+//    construction and security proof used to create XSalsa20, an extended-
+//    nonce Salsa20 variant used in NaCl.
+
+//    HChaCha20 is initialized the same way as the ChaCha cipher, except
+//    that HChaCha20 uses a 128-bit nonce and has no counter.
+
+//    After initialization, proceed through the ChaCha rounds as usual.
+
+//    Once the 20 ChaCha rounds have been completed, the first 128 bits and
+//    last 128 bits of the ChaCha state (both little-endian) are
+//    concatenated, and this 256-bit subkey is returned.
+
+/// Derives the XChaCha20 subkey from `key` and the first 16 bytes of an
+/// extended nonce.
+pub fn hchacha20(key: &[u8; 32], nonce: &[u8; 16]) -> [u8; 32] {
+    // The 128-bit nonce takes the place of the block counter and the 96-bit
+    // nonce, so `init_state` can lay it out.
+    let mut counter = [0u8; 4];
+    counter.copy_from_slice(&nonce[..4]);
+    let mut rest = [0u8; 12];
+    rest.copy_from_slice(&nonce[4..]);
+
+    let mut x = init_state(key, u32::from_le_bytes(counter), &rest);
+    chacha_rounds::<20>(&mut x);
+
+    let mut subkey = [0u8; 32];
+    for (word, bytes) in x[..4]
+        .iter()
+        .chain(x[12..].iter())
+        .zip(subkey.chunks_exact_mut(4))
+    {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    wipe(&mut x);
+
+    subkey
+}
+
+// 2.2.1.  Test Vector for the HChaCha20 Block Function
+
+#[test]
+fn test_hchacha20() {
+    // o  Key = 00:01:02:03:04:05:06:07:08:09:0a:0b:0c:0d:0e:0f:10:11:12:13:
+    //    14:15:16:17:18:19:1a:1b:1c:1d:1e:1f.  The key is a sequence of
+    //    octets with no particular structure before we copy it into the
+    //    HChaCha state.
+    // o  Nonce = (00:00:00:09:00:00:00:4a:00:00:00:00:31:41:59:27)
+    let key: [u8; 32] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
+        0x1e, 0x1f,
+    ];
+    let nonce: [u8; 16] = [
+        0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00, 0x31, 0x41, 0x59,
+        0x27,
+    ];
+
+    // Resulting HChaCha20 Subkey
+    // 82413b42 27b27bfe d30e4250 8a877d73
+    // a0f9e4d5 8a74a853 c12ec413 26d3ecdc
+    let expected: [u8; 32] = [
+        0x82, 0x41, 0x3b, 0x42, 0x27, 0xb2, 0x7b, 0xfe, 0xd3, 0x0e, 0x42, 0x50, 0x8a, 0x87, 0x7d,
+        0x73, 0xa0, 0xf9, 0xe4, 0xd5, 0x8a, 0x74, 0xa8, 0x53, 0xc1, 0x2e, 0xc4, 0x13, 0x26, 0xd3,
+        0xec, 0xdc,
+    ];
+
+    assert_eq!(hchacha20(&key, &nonce), expected);
+}
@@ -0,0 +1,1256 @@
+use crate::aead::constant_time_eq;
+use crate::backend::xor_keystream;
+#[cfg(feature = "std")]
+use crate::chacha20::chacha_encrypt_parallel;
+use crate::chacha20::{
+    chacha_encrypt_in_place, chacha_legacy_encrypt_in_place, check_counter, hchacha20, init_state,
+    init_state_legacy, keystream_block,
+};
+#[cfg(feature = "alloc")]
+use crate::encoding::{decode_base64, decode_hex};
+use crate::error::ChaChaError;
+use crate::wipe::wipe;
+#[cfg(test)]
+use alloc::vec;
+#[cfg(any(feature = "alloc", test))]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+// With a 32-bit block counter the key stream is 2^32 blocks of 64 bytes.
+const KEYSTREAM_LEN: u64 = (1 << 32) * 64;
+
+/// A 256-bit ChaCha20 key.  The bytes are wiped when the key is dropped, and
+/// `==` takes the same time wherever two keys differ.
+#[derive(Clone)]
+pub struct Key([u8; 32]);
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Key) -> bool {
+        constant_time_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Key {}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+impl Key {
+    pub fn new(bytes: [u8; 32]) -> Key {
+        Key(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Parses a key written as 64 hex digits.
+    #[cfg(feature = "alloc")]
+    pub fn from_hex(s: &str) -> Result<Key, ChaChaError> {
+        let mut bytes = decode_hex(s)?;
+        let key = Key::try_from(&bytes[..]);
+        wipe(&mut bytes);
+        key
+    }
+
+    /// Parses a key written in standard base64, with or without padding.
+    #[cfg(feature = "alloc")]
+    pub fn from_base64(s: &str) -> Result<Key, ChaChaError> {
+        let mut bytes = decode_base64(s)?;
+        let key = Key::try_from(&bytes[..]);
+        wipe(&mut bytes);
+        key
+    }
+}
+
+impl From<[u8; 32]> for Key {
+    fn from(bytes: [u8; 32]) -> Key {
+        Key(bytes)
+    }
+}
+
+impl TryFrom<&[u8]> for Key {
+    type Error = ChaChaError;
+
+    /// Fails with `InvalidKeyLength` unless `bytes` is exactly 32 bytes.
+    fn try_from(bytes: &[u8]) -> Result<Key, ChaChaError> {
+        <[u8; 32]>::try_from(bytes)
+            .map(Key)
+            .map_err(|_| ChaChaError::InvalidKeyLength(bytes.len()))
+    }
+}
+
+/// A 96-bit ChaCha20 nonce, which should not be repeated for the same key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Nonce([u8; 12]);
+
+impl Nonce {
+    pub fn new(bytes: [u8; 12]) -> Nonce {
+        Nonce(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 12] {
+        &self.0
+    }
+
+    /// Parses a nonce written as 24 hex digits.
+    #[cfg(feature = "alloc")]
+    pub fn from_hex(s: &str) -> Result<Nonce, ChaChaError> {
+        Nonce::try_from(&decode_hex(s)?[..])
+    }
+
+    /// Parses a nonce written in standard base64, with or without padding.
+    #[cfg(feature = "alloc")]
+    pub fn from_base64(s: &str) -> Result<Nonce, ChaChaError> {
+        Nonce::try_from(&decode_base64(s)?[..])
+    }
+}
+
+impl From<[u8; 12]> for Nonce {
+    fn from(bytes: [u8; 12]) -> Nonce {
+        Nonce(bytes)
+    }
+}
+
+impl TryFrom<&[u8]> for Nonce {
+    type Error = ChaChaError;
+
+    /// Fails with `InvalidNonceLength` unless `bytes` is exactly 12 bytes.
+    fn try_from(bytes: &[u8]) -> Result<Nonce, ChaChaError> {
+        <[u8; 12]>::try_from(bytes)
+            .map(Nonce)
+            .map_err(|_| ChaChaError::InvalidNonceLength(bytes.len()))
+    }
+}
+
+/// The ChaCha stream cipher with `ROUNDS` rounds, using the RFC 8439 state
+/// layout.  Use the `ChaCha20`, `ChaCha12` and `ChaCha8` aliases.
+///
+/// Besides the one-shot `encrypt`/`decrypt`, the cipher keeps a position in
+/// the key stream so that a long message can be processed in pieces with
+/// `apply_keystream`.  Position 0 is the first byte of block 0.
+///
+/// The key and any buffered key stream are wiped when the cipher is dropped.
+pub struct ChaCha<const ROUNDS: usize> {
+    key: Key,
+    nonce: Nonce,
+    // The key stream block containing `pos`, valid when `pos` is not at a
+    // block boundary.
+    block: [u8; 64],
+    pos: u64,
+}
+
+/// The ChaCha20 stream cipher of RFC 8439 section 2.4.
+pub type ChaCha20 = ChaCha<20>;
+
+/// ChaCha reduced to 12 rounds, for output such as RNGs where speed matters
+/// more than the full security margin.
+pub type ChaCha12 = ChaCha<12>;
+
+/// ChaCha reduced to 8 rounds.  Only for non-adversarial uses such as
+/// checksum-style key streams.
+pub type ChaCha8 = ChaCha<8>;
+
+impl<const ROUNDS: usize> Drop for ChaCha<ROUNDS> {
+    fn drop(&mut self) {
+        wipe(&mut self.block);
+    }
+}
+
+impl<const ROUNDS: usize> ChaCha<ROUNDS> {
+    pub fn new(key: &Key, nonce: &Nonce) -> ChaCha<ROUNDS> {
+        ChaCha {
+            key: key.clone(),
+            nonce: *nonce,
+            block: [0; 64],
+            pos: 0,
+        }
+    }
+
+    /// Encrypts `plaintext` with the key stream starting at block `counter`.
+    ///
+    /// Fails with `CounterExhausted` if the message would need blocks past
+    /// counter 2^32 - 1.
+    #[cfg(feature = "alloc")]
+    pub fn encrypt(&self, counter: u32, plaintext: &[u8]) -> Result<Vec<u8>, ChaChaError> {
+        let mut ciphertext = plaintext.to_vec();
+        self.encrypt_in_place(counter, &mut ciphertext)?;
+        Ok(ciphertext)
+    }
+
+    /// Decrypts `ciphertext`.  ChaCha is symmetric, so this is the same
+    /// operation as `encrypt`.
+    #[cfg(feature = "alloc")]
+    pub fn decrypt(&self, counter: u32, ciphertext: &[u8]) -> Result<Vec<u8>, ChaChaError> {
+        self.encrypt(counter, ciphertext)
+    }
+
+    /// Encrypts (or decrypts) `data` in place with the key stream starting
+    /// at block `counter`.  No heap allocation is made, and `data` is left
+    /// untouched on error.
+    pub fn encrypt_in_place(&self, counter: u32, data: &mut [u8]) -> Result<(), ChaChaError> {
+        chacha_encrypt_in_place::<ROUNDS>(&self.key.0, counter, &self.nonce.0, data)
+    }
+
+    /// Same as `encrypt_in_place`, but a large `data` is split at block
+    /// boundaries and encrypted on up to `threads` threads, or one per CPU
+    /// if `threads` is 0.  The result is identical to `encrypt_in_place`.
+    #[cfg(feature = "std")]
+    pub fn encrypt_parallel(
+        &self,
+        counter: u32,
+        data: &mut [u8],
+        threads: usize,
+    ) -> Result<(), ChaChaError> {
+        chacha_encrypt_parallel::<ROUNDS>(&self.key.0, counter, &self.nonce.0, data, threads)
+    }
+
+    /// Encrypts (or decrypts) `src` into `dst` with the key stream starting
+    /// at block `counter`.  No heap allocation is made.
+    ///
+    /// Fails with `LengthMismatch` if `src` and `dst` differ in length.
+    pub fn encrypt_into(
+        &self,
+        counter: u32,
+        src: &[u8],
+        dst: &mut [u8],
+    ) -> Result<(), ChaChaError> {
+        if src.len() != dst.len() {
+            return Err(ChaChaError::LengthMismatch);
+        }
+
+        check_counter(u128::from(counter), src.len(), 1 << 32)?;
+        dst.copy_from_slice(src);
+        self.encrypt_in_place(counter, dst)
+    }
+
+    /// XORs `data` with the key stream at the current position and advances
+    /// the position by `data.len()` bytes.
+    ///
+    /// Fails with `CounterExhausted`, leaving `data` and the position alone,
+    /// if `data` runs past the end of the 256 GiB key stream.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) -> Result<(), ChaChaError> {
+        if data.len() as u64 > KEYSTREAM_LEN - self.pos {
+            return Err(ChaChaError::CounterExhausted);
+        }
+
+        let (key, nonce) = (&self.key, &self.nonce);
+        xor_buffered(&mut self.block, u128::from(self.pos), data, |index, buf| {
+            let mut state = init_state(&key.0, index as u32, &nonce.0);
+            xor_keystream::<ROUNDS>(&state, buf);
+            wipe(&mut state);
+        });
+        self.pos += data.len() as u64;
+        Ok(())
+    }
+
+    /// Moves to byte `pos` of the key stream.
+    ///
+    /// Fails with `CounterExhausted` if `pos` is past the end of the 256 GiB
+    /// key stream.
+    pub fn seek(&mut self, pos: u64) -> Result<(), ChaChaError> {
+        if pos > KEYSTREAM_LEN {
+            return Err(ChaChaError::CounterExhausted);
+        }
+
+        self.pos = pos;
+        if !pos.is_multiple_of(64) {
+            let mut state = init_state(&self.key.0, (pos / 64) as u32, &self.nonce.0);
+            keystream_block::<ROUNDS>(&state, &mut self.block);
+            wipe(&mut state);
+        }
+        Ok(())
+    }
+
+    /// Returns the current byte position in the key stream.
+    pub fn current_pos(&self) -> u64 {
+        self.pos
+    }
+}
+
+// XORs `data` with the key stream from byte `pos` on.  `block` must hold the
+// key stream block containing `pos` unless `pos` is at a block boundary;
+// `xor` XORs the key stream starting at the given block index into a buffer.
+//
+// Whole blocks are handed to `xor` in one piece so that the SIMD backends
+// can work on them; only a partial block at either end goes through `block`.
+fn xor_buffered<F>(block: &mut [u8; 64], pos: u128, data: &mut [u8], mut xor: F)
+where
+    F: FnMut(u128, &mut [u8]),
+{
+    let offset = (pos % 64) as usize;
+    let n = if offset == 0 {
+        0
+    } else {
+        (64 - offset).min(data.len())
+    };
+    let (head, data) = data.split_at_mut(n);
+    for (d, k) in head.iter_mut().zip(&block[offset..]) {
+        *d ^= k;
+    }
+
+    let pos = pos + n as u128;
+    let whole = data.len() - data.len() % 64;
+    let (middle, tail) = data.split_at_mut(whole);
+    xor(pos / 64, middle);
+
+    if !tail.is_empty() {
+        *block = [0; 64];
+        xor((pos + whole as u128) / 64, block);
+        for (d, k) in tail.iter_mut().zip(block.iter()) {
+            *d ^= k;
+        }
+    }
+}
+
+/// A 64-bit nonce for the original ChaCha layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LegacyNonce([u8; 8]);
+
+impl LegacyNonce {
+    pub fn new(bytes: [u8; 8]) -> LegacyNonce {
+        LegacyNonce(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+}
+
+impl From<[u8; 8]> for LegacyNonce {
+    fn from(bytes: [u8; 8]) -> LegacyNonce {
+        LegacyNonce(bytes)
+    }
+}
+
+impl TryFrom<&[u8]> for LegacyNonce {
+    type Error = ChaChaError;
+
+    fn try_from(bytes: &[u8]) -> Result<LegacyNonce, ChaChaError> {
+        <[u8; 8]>::try_from(bytes)
+            .map(LegacyNonce)
+            .map_err(|_| ChaChaError::InvalidNonceLength(bytes.len()))
+    }
+}
+
+// With a 64-bit block counter the key stream is 2^64 blocks of 64 bytes.
+const LEGACY_KEYSTREAM_LEN: u128 = (1 << 64) * 64;
+
+/// The original ChaCha by D. J. Bernstein, with a 64-bit block counter and a
+/// 64-bit nonce, for interoperating with implementations that predate
+/// RFC 8439.  Use the `ChaCha20Legacy` alias for the 20-round cipher.
+///
+/// The 64-bit counter makes the key stream 2^70 bytes long, so positions are
+/// `u128` byte offsets.  Otherwise this behaves like `ChaCha`.
+pub struct ChaChaLegacy<const ROUNDS: usize> {
+    key: Key,
+    nonce: LegacyNonce,
+    block: [u8; 64],
+    pos: u128,
+}
+
+/// The original 20-round ChaCha with a 64-bit nonce.
+pub type ChaCha20Legacy = ChaChaLegacy<20>;
+
+impl<const ROUNDS: usize> Drop for ChaChaLegacy<ROUNDS> {
+    fn drop(&mut self) {
+        wipe(&mut self.block);
+    }
+}
+
+impl<const ROUNDS: usize> ChaChaLegacy<ROUNDS> {
+    pub fn new(key: &Key, nonce: &LegacyNonce) -> ChaChaLegacy<ROUNDS> {
+        ChaChaLegacy {
+            key: key.clone(),
+            nonce: *nonce,
+            block: [0; 64],
+            pos: 0,
+        }
+    }
+
+    /// Encrypts `plaintext` with the key stream starting at block `counter`.
+    #[cfg(feature = "alloc")]
+    pub fn encrypt(&self, counter: u64, plaintext: &[u8]) -> Result<Vec<u8>, ChaChaError> {
+        let mut ciphertext = plaintext.to_vec();
+        self.encrypt_in_place(counter, &mut ciphertext)?;
+        Ok(ciphertext)
+    }
+
+    /// Decrypts `ciphertext`, the same operation as `encrypt`.
+    #[cfg(feature = "alloc")]
+    pub fn decrypt(&self, counter: u64, ciphertext: &[u8]) -> Result<Vec<u8>, ChaChaError> {
+        self.encrypt(counter, ciphertext)
+    }
+
+    /// See `ChaCha::encrypt_in_place`.
+    pub fn encrypt_in_place(&self, counter: u64, data: &mut [u8]) -> Result<(), ChaChaError> {
+        chacha_legacy_encrypt_in_place::<ROUNDS>(&self.key.0, counter, &self.nonce.0, data)
+    }
+
+    /// See `ChaCha::encrypt_into`.
+    pub fn encrypt_into(
+        &self,
+        counter: u64,
+        src: &[u8],
+        dst: &mut [u8],
+    ) -> Result<(), ChaChaError> {
+        if src.len() != dst.len() {
+            return Err(ChaChaError::LengthMismatch);
+        }
+
+        check_counter(u128::from(counter), src.len(), 1 << 64)?;
+        dst.copy_from_slice(src);
+        self.encrypt_in_place(counter, dst)
+    }
+
+    /// See `ChaCha::apply_keystream`.
+    ///
+    /// Fails with `CounterExhausted` if `data` runs past the end of the
+    /// 2^70 byte key stream.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) -> Result<(), ChaChaError> {
+        if data.len() as u128 > LEGACY_KEYSTREAM_LEN - self.pos {
+            return Err(ChaChaError::CounterExhausted);
+        }
+
+        let (key, nonce) = (&self.key, &self.nonce);
+        xor_buffered(&mut self.block, self.pos, data, |index, buf| {
+            let mut state = init_state_legacy(&key.0, index as u64, &nonce.0);
+            xor_keystream::<ROUNDS>(&state, buf);
+            wipe(&mut state);
+        });
+        self.pos += data.len() as u128;
+        Ok(())
+    }
+
+    /// Moves to byte `pos` of the key stream.
+    ///
+    /// Fails with `CounterExhausted` if `pos` is past the end of the 2^70
+    /// byte key stream.
+    pub fn seek(&mut self, pos: u128) -> Result<(), ChaChaError> {
+        if pos > LEGACY_KEYSTREAM_LEN {
+            return Err(ChaChaError::CounterExhausted);
+        }
+
+        self.pos = pos;
+        if !pos.is_multiple_of(64) {
+            let mut state = init_state_legacy(&self.key.0, (pos / 64) as u64, &self.nonce.0);
+            keystream_block::<ROUNDS>(&state, &mut self.block);
+            wipe(&mut state);
+        }
+        Ok(())
+    }
+
+    /// Returns the current byte position in the key stream.
+    pub fn current_pos(&self) -> u128 {
+        self.pos
+    }
+}
+
+/// A 192-bit XChaCha20 nonce, long enough to be chosen at random.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XNonce([u8; 24]);
+
+impl XNonce {
+    pub fn new(bytes: [u8; 24]) -> XNonce {
+        XNonce(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 24] {
+        &self.0
+    }
+}
+
+impl From<[u8; 24]> for XNonce {
+    fn from(bytes: [u8; 24]) -> XNonce {
+        XNonce(bytes)
+    }
+}
+
+impl TryFrom<&[u8]> for XNonce {
+    type Error = ChaChaError;
+
+    fn try_from(bytes: &[u8]) -> Result<XNonce, ChaChaError> {
+        <[u8; 24]>::try_from(bytes)
+            .map(XNonce)
+            .map_err(|_| ChaChaError::InvalidNonceLength(bytes.len()))
+    }
+}
+
+/// XChaCha20 from draft-irtf-cfrg-xchacha: ChaCha20 with a 192-bit nonce.
+///
+/// The first 128 bits of the nonce and the key are passed through
+/// `hchacha20` to derive a subkey, and the remaining 64 bits, prefixed with
+/// four zero bytes, become the ChaCha20 nonce.  Everything else behaves
+/// exactly like `ChaCha20`.
+pub struct XChaCha20 {
+    inner: ChaCha20,
+}
+
+impl XChaCha20 {
+    pub fn new(key: &Key, nonce: &XNonce) -> XChaCha20 {
+        let (subkey, chacha_nonce) = xchacha20_subkey(key, nonce);
+
+        XChaCha20 {
+            inner: ChaCha20::new(&subkey, &chacha_nonce),
+        }
+    }
+
+    /// Encrypts `plaintext` with the key stream starting at block `counter`.
+    #[cfg(feature = "alloc")]
+    pub fn encrypt(&self, counter: u32, plaintext: &[u8]) -> Result<Vec<u8>, ChaChaError> {
+        self.inner.encrypt(counter, plaintext)
+    }
+
+    /// Decrypts `ciphertext`, the same operation as `encrypt`.
+    #[cfg(feature = "alloc")]
+    pub fn decrypt(&self, counter: u32, ciphertext: &[u8]) -> Result<Vec<u8>, ChaChaError> {
+        self.inner.decrypt(counter, ciphertext)
+    }
+
+    /// See `ChaCha20::encrypt_in_place`.
+    pub fn encrypt_in_place(&self, counter: u32, data: &mut [u8]) -> Result<(), ChaChaError> {
+        self.inner.encrypt_in_place(counter, data)
+    }
+
+    /// See `ChaCha20::encrypt_parallel`.
+    #[cfg(feature = "std")]
+    pub fn encrypt_parallel(
+        &self,
+        counter: u32,
+        data: &mut [u8],
+        threads: usize,
+    ) -> Result<(), ChaChaError> {
+        self.inner.encrypt_parallel(counter, data, threads)
+    }
+
+    /// See `ChaCha20::encrypt_into`.
+    pub fn encrypt_into(
+        &self,
+        counter: u32,
+        src: &[u8],
+        dst: &mut [u8],
+    ) -> Result<(), ChaChaError> {
+        self.inner.encrypt_into(counter, src, dst)
+    }
+
+    /// See `ChaCha20::apply_keystream`.
+    pub fn apply_keystream(&mut self, data: &mut [u8]) -> Result<(), ChaChaError> {
+        self.inner.apply_keystream(data)
+    }
+
+    /// See `ChaCha20::seek`.
+    pub fn seek(&mut self, pos: u64) -> Result<(), ChaChaError> {
+        self.inner.seek(pos)
+    }
+
+    /// See `ChaCha20::current_pos`.
+    pub fn current_pos(&self) -> u64 {
+        self.inner.current_pos()
+    }
+}
+
+// 2.3.  XChaCha20
+//
+//    1.  Calculate a subkey from the first 16 bytes of the nonce and the
+//        key, using HChaCha20 (Section 2.2).
+//    2.  Use the subkey and remaining 8 bytes of the nonce (prefixed with
+//        4 NUL bytes) with AEAD_CHACHA20_POLY1305 from [RFC8439] as normal.
+pub(crate) fn xchacha20_subkey(key: &Key, nonce: &XNonce) -> (Key, Nonce) {
+    let mut hnonce = [0u8; 16];
+    hnonce.copy_from_slice(&nonce.0[..16]);
+
+    let mut chacha_nonce = [0u8; 12];
+    chacha_nonce[4..].copy_from_slice(&nonce.0[16..]);
+
+    let mut subkey = hchacha20(&key.0, &hnonce);
+    let key = Key::new(subkey);
+    wipe(&mut subkey);
+
+    (key, Nonce::new(chacha_nonce))
+}
+
+#[cfg(all(test, feature = "alloc"))]
+use crate::chacha20::chacha20_encrypt;
+#[cfg(test)]
+use core::mem::ManuallyDrop;
+
+#[cfg(test)]
+fn rfc_key() -> Key {
+    // Key = 00:01:02:03:04:05:06:07:08:09:0a:0b:0c:0d:0e:0f:10:11:12:13:
+    // 14:15:16:17:18:19:1a:1b:1c:1d:1e:1f.
+    let mut key = [0u8; 32];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    Key::new(key)
+}
+
+#[test]
+fn test_key_try_from() {
+    let bytes: Vec<u8> = (0..33).collect();
+
+    assert!(Key::try_from(&bytes[..32]) == Ok(rfc_key()));
+    assert!(Key::try_from(&bytes[..31]) == Err(ChaChaError::InvalidKeyLength(31)));
+    assert!(Key::try_from(&bytes[..]) == Err(ChaChaError::InvalidKeyLength(33)));
+    assert!(Key::try_from(&[][..]) == Err(ChaChaError::InvalidKeyLength(0)));
+}
+
+#[test]
+fn test_key_eq() {
+    let mut bytes = *rfc_key().as_bytes();
+    assert!(Key::new(bytes) == rfc_key());
+
+    for i in [0, 17, 31].iter() {
+        bytes[*i] ^= 1;
+        assert!(Key::new(bytes) != rfc_key(), "byte {}", i);
+        bytes[*i] ^= 1;
+    }
+}
+
+#[test]
+fn test_wiped_on_drop() {
+    // Dropping in place leaves the memory behind, so what the destructors
+    // wrote can be checked.
+    let mut key = ManuallyDrop::new(rfc_key());
+    unsafe { ManuallyDrop::drop(&mut key) };
+    assert_eq!(key.0, [0; 32]);
+
+    let mut cipher = ManuallyDrop::new(ChaCha20::new(&rfc_key(), &Nonce::new([0; 12])));
+    cipher.apply_keystream(&mut [0; 10]).unwrap();
+    assert_ne!(cipher.block, [0; 64]);
+    unsafe { ManuallyDrop::drop(&mut cipher) };
+    assert_eq!(cipher.key.0, [0; 32]);
+    assert_eq!(cipher.block, [0; 64]);
+}
+
+#[test]
+fn test_nonce_try_from() {
+    let bytes: Vec<u8> = (0..25).collect();
+
+    assert_eq!(
+        Nonce::try_from(&bytes[..12]).map(|n| *n.as_bytes()),
+        Ok([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11])
+    );
+    assert_eq!(
+        Nonce::try_from(&bytes[..11]),
+        Err(ChaChaError::InvalidNonceLength(11))
+    );
+    assert_eq!(
+        LegacyNonce::try_from(&bytes[..12]),
+        Err(ChaChaError::InvalidNonceLength(12))
+    );
+    assert!(XNonce::try_from(&bytes[..24]).is_ok());
+    assert_eq!(
+        XNonce::try_from(&bytes[..]),
+        Err(ChaChaError::InvalidNonceLength(25))
+    );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_key_from_hex() {
+    let hex = "000102030405060708090a0b0c0d0e0f101112131415161718191A1B1C1D1E1F";
+    assert!(Key::from_hex(hex) == Ok(rfc_key()));
+
+    assert!(Key::from_hex(&hex[..62]) == Err(ChaChaError::InvalidKeyLength(31)));
+    assert!(Key::from_hex(&hex[..63]) == Err(ChaChaError::InvalidHex(63)));
+    let bad = hex.replace("0a", "0x");
+    assert!(Key::from_hex(&bad) == Err(ChaChaError::InvalidHex(21)));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_key_from_base64() {
+    let b64 = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+    assert!(Key::from_base64(b64) == Ok(rfc_key()));
+    assert!(Key::from_base64(b64.trim_end_matches('=')) == Ok(rfc_key()));
+
+    assert!(Key::from_base64("AAEC") == Err(ChaChaError::InvalidKeyLength(3)));
+    assert!(Key::from_base64("AAEC AwQFBgc") == Err(ChaChaError::InvalidBase64(4)));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_nonce_from_hex_and_base64() {
+    let expected = Nonce::new([
+        0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
+    ]);
+
+    assert_eq!(Nonce::from_hex("000000090000004a00000000"), Ok(expected));
+    assert_eq!(Nonce::from_base64("AAAACQAAAEoAAAAA"), Ok(expected));
+    assert_eq!(
+        Nonce::from_hex("000000090000004a0000000000"),
+        Err(ChaChaError::InvalidNonceLength(13))
+    );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_block_through_cipher() {
+    // 2.3.2.  Test Vector for the ChaCha20 Block Function
+    //
+    // Encrypting an all-zero block exposes the serialized key stream.
+    let nonce = Nonce::new([
+        0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
+    ]);
+    let cipher = ChaCha20::new(&rfc_key(), &nonce);
+
+    let expected = vec![
+        0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15, 0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20, 0x71,
+        0xc4, 0xc7, 0xd1, 0xf4, 0xc7, 0x33, 0xc0, 0x68, 0x03, 0x04, 0x22, 0xaa, 0x9a, 0xc3, 0xd4,
+        0x6c, 0x4e, 0xd2, 0x82, 0x64, 0x46, 0x07, 0x9f, 0xaa, 0x09, 0x14, 0xc2, 0xd7, 0x05, 0xd9,
+        0x8b, 0x02, 0xa2, 0xb5, 0x12, 0x9c, 0xd1, 0xde, 0x16, 0x4e, 0xb9, 0xcb, 0xd0, 0x83, 0xe8,
+        0xa2, 0x50, 0x3c, 0x4e,
+    ];
+
+    assert_eq!(cipher.encrypt(1, &[0; 64]).unwrap(), expected);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_encrypt_sunscreen() {
+    // 2.4.2.  Example and Test Vector for the ChaCha20 Cipher
+    let nonce = Nonce::new([
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
+    ]);
+    let cipher = ChaCha20::new(&rfc_key(), &nonce);
+
+    let plaintext = b"Ladies and Gentlemen of the class of '99: \
+If I could offer you only one tip for the future, sunscreen would be it.";
+
+    let ciphertext = vec![
+        0x6e, 0x2e, 0x35, 0x9a, 0x25, 0x68, 0xf9, 0x80, 0x41, 0xba, 0x07, 0x28, 0xdd, 0x0d, 0x69,
+        0x81, 0xe9, 0x7e, 0x7a, 0xec, 0x1d, 0x43, 0x60, 0xc2, 0x0a, 0x27, 0xaf, 0xcc, 0xfd, 0x9f,
+        0xae, 0x0b, 0xf9, 0x1b, 0x65, 0xc5, 0x52, 0x47, 0x33, 0xab, 0x8f, 0x59, 0x3d, 0xab, 0xcd,
+        0x62, 0xb3, 0x57, 0x16, 0x39, 0xd6, 0x24, 0xe6, 0x51, 0x52, 0xab, 0x8f, 0x53, 0x0c, 0x35,
+        0x9f, 0x08, 0x61, 0xd8, 0x07, 0xca, 0x0d, 0xbf, 0x50, 0x0d, 0x6a, 0x61, 0x56, 0xa3, 0x8e,
+        0x08, 0x8a, 0x22, 0xb6, 0x5e, 0x52, 0xbc, 0x51, 0x4d, 0x16, 0xcc, 0xf8, 0x06, 0x81, 0x8c,
+        0xe9, 0x1a, 0xb7, 0x79, 0x37, 0x36, 0x5a, 0xf9, 0x0b, 0xbf, 0x74, 0xa3, 0x5b, 0xe6, 0xb4,
+        0x0b, 0x8e, 0xed, 0xf2, 0x78, 0x5e, 0x42, 0x87, 0x4d,
+    ];
+
+    assert_eq!(cipher.encrypt(1, plaintext).unwrap(), ciphertext);
+    assert_eq!(cipher.decrypt(1, &ciphertext).unwrap(), plaintext.to_vec());
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_encrypt_one_byte_past_block() {
+    // A message one byte longer than a block must still have its last byte
+    // encrypted with the first byte of the following key stream block.
+    let nonce = Nonce::new([0; 12]);
+    let cipher = ChaCha20::new(&rfc_key(), &nonce);
+
+    let actual = cipher.encrypt(1, &[0; 65]).unwrap();
+    let second_block = cipher.encrypt(2, &[0; 1]).unwrap();
+
+    assert_eq!(actual[..64], cipher.encrypt(1, &[0; 64]).unwrap()[..]);
+    assert_eq!(actual[64], second_block[0]);
+    assert_ne!(actual[64], 0);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_apply_keystream_any_split() {
+    let nonce = Nonce::new([
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
+    ]);
+    let plaintext: Vec<u8> = (0..300).map(|i| i as u8).collect();
+    let expected = ChaCha20::new(&rfc_key(), &nonce)
+        .encrypt(0, &plaintext)
+        .unwrap();
+
+    for chunk in 1..=130 {
+        let mut cipher = ChaCha20::new(&rfc_key(), &nonce);
+        let mut buf = plaintext.clone();
+        for piece in buf.chunks_mut(chunk) {
+            cipher.apply_keystream(piece).unwrap();
+        }
+
+        assert_eq!(buf, expected, "chunk size {}", chunk);
+        assert_eq!(cipher.current_pos(), 300);
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_seek() {
+    let nonce = Nonce::new([
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
+    ]);
+    let plaintext = [0x5a; 200];
+    let expected = ChaCha20::new(&rfc_key(), &nonce)
+        .encrypt(0, &plaintext)
+        .unwrap();
+
+    let mut cipher = ChaCha20::new(&rfc_key(), &nonce);
+    for &pos in &[0u64, 1, 63, 64, 65, 127, 150, 199, 200] {
+        cipher.seek(pos).unwrap();
+        assert_eq!(cipher.current_pos(), pos);
+
+        let mut buf = plaintext[pos as usize..].to_vec();
+        cipher.apply_keystream(&mut buf).unwrap();
+        assert_eq!(buf, expected[pos as usize..].to_vec(), "seek to {}", pos);
+    }
+
+    // Seeking to block 1 lines up with the RFC's initial block counter.
+    cipher.seek(64).unwrap();
+    let mut buf = [0; 64];
+    cipher.apply_keystream(&mut buf).unwrap();
+    assert_eq!(
+        buf.to_vec(),
+        ChaCha20::new(&rfc_key(), &nonce)
+            .encrypt(1, &[0; 64])
+            .unwrap()
+    );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_seek_to_last_block() {
+    let nonce = Nonce::new([0; 12]);
+    let mut cipher = ChaCha20::new(&rfc_key(), &nonce);
+    cipher.seek(KEYSTREAM_LEN - 10).unwrap();
+
+    let mut buf = [0; 10];
+    cipher.apply_keystream(&mut buf).unwrap();
+    assert_eq!(cipher.current_pos(), KEYSTREAM_LEN);
+    assert_eq!(buf[..], cipher.encrypt(u32::MAX, &[0; 64]).unwrap()[54..]);
+}
+
+#[test]
+fn test_apply_keystream_past_end() {
+    let nonce = Nonce::new([0; 12]);
+    let mut cipher = ChaCha20::new(&rfc_key(), &nonce);
+    cipher.seek(KEYSTREAM_LEN - 10).unwrap();
+
+    let mut buf = [0; 11];
+    assert_eq!(
+        cipher.apply_keystream(&mut buf),
+        Err(ChaChaError::CounterExhausted)
+    );
+    assert_eq!(buf, [0; 11]);
+    assert_eq!(cipher.current_pos(), KEYSTREAM_LEN - 10);
+
+    // The remaining ten bytes can still be used, and then nothing more.
+    cipher.apply_keystream(&mut buf[..10]).unwrap();
+    assert_eq!(
+        cipher.apply_keystream(&mut [0; 1]),
+        Err(ChaChaError::CounterExhausted)
+    );
+    assert_eq!(
+        cipher.seek(KEYSTREAM_LEN + 1),
+        Err(ChaChaError::CounterExhausted)
+    );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_encrypt_counter_exhausted() {
+    let cipher = ChaCha20::new(&rfc_key(), &Nonce::new([0; 12]));
+
+    assert!(cipher.encrypt(u32::MAX, &[0; 64]).is_ok());
+    assert_eq!(
+        cipher.encrypt(u32::MAX, &[0; 65]),
+        Err(ChaChaError::CounterExhausted)
+    );
+
+    let mut dst = [0x11; 65];
+    assert_eq!(
+        cipher.encrypt_into(u32::MAX, &[0; 65], &mut dst),
+        Err(ChaChaError::CounterExhausted)
+    );
+    assert_eq!(dst, [0x11; 65]);
+
+    let xcipher = XChaCha20::new(&rfc_key(), &XNonce::new([0; 24]));
+    assert_eq!(
+        xcipher.encrypt(u32::MAX, &[0; 65]),
+        Err(ChaChaError::CounterExhausted)
+    );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_encrypt_in_place() {
+    let nonce = Nonce::new([
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
+    ]);
+    let cipher = ChaCha20::new(&rfc_key(), &nonce);
+    let plaintext: Vec<u8> = (0..200).map(|i| i as u8).collect();
+    let expected = chacha20_encrypt(&rfc_key().0, 1, &nonce.0, plaintext.clone()).unwrap();
+
+    let mut buf = plaintext.clone();
+    cipher.encrypt_in_place(1, &mut buf).unwrap();
+    assert_eq!(buf, expected);
+
+    cipher.encrypt_in_place(1, &mut buf).unwrap();
+    assert_eq!(buf, plaintext);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_encrypt_parallel() {
+    let nonce = Nonce::new([3; 12]);
+    let plaintext: Vec<u8> = (0..300 * 1024 + 5).map(|i| i as u8).collect();
+
+    let cipher = ChaCha12::new(&rfc_key(), &nonce);
+    let mut expected = plaintext.clone();
+    cipher.encrypt_in_place(9, &mut expected).unwrap();
+    let mut buf = plaintext.clone();
+    cipher.encrypt_parallel(9, &mut buf, 4).unwrap();
+    assert!(buf == expected);
+
+    let cipher = XChaCha20::new(&rfc_key(), &XNonce::new([5; 24]));
+    let mut expected = plaintext.clone();
+    cipher.encrypt_in_place(0, &mut expected).unwrap();
+    let mut buf = plaintext;
+    cipher.encrypt_parallel(0, &mut buf, 0).unwrap();
+    assert!(buf == expected);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_encrypt_into() {
+    let nonce = Nonce::new([
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
+    ]);
+    let cipher = ChaCha20::new(&rfc_key(), &nonce);
+    let plaintext: Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+    let mut dst = [0u8; 200];
+    cipher.encrypt_into(1, &plaintext, &mut dst).unwrap();
+    assert_eq!(dst.to_vec(), cipher.encrypt(1, &plaintext).unwrap());
+}
+
+#[test]
+fn test_encrypt_into_length_mismatch() {
+    let cipher = ChaCha20::new(&rfc_key(), &Nonce::new([0; 12]));
+    assert_eq!(
+        cipher.encrypt_into(1, &[0; 10], &mut [0; 9]),
+        Err(ChaChaError::LengthMismatch)
+    );
+}
+
+// draft-irtf-cfrg-xchacha, A.3.2.  Example and Test Vector for XChaCha20
+//
+// The published vector starts at the second key stream block (counter 1).
+
+#[cfg(test)]
+const DHOLE: &[u8] = b"The dhole (pronounced \"dole\") is also known as the Asiatic wild dog, \
+red dog, and whistling dog. It is about the size of a German shepherd but looks more like a \
+long-legged fox. This highly elusive and skilled jumper is classified with wolves, coyotes, \
+jackals, and foxes in the taxonomic family Canidae.";
+
+#[cfg(test)]
+fn dhole_vector() -> (Key, XNonce, Vec<u8>) {
+    // Key: 808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f
+    let mut key = [0u8; 32];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = 0x80 + i as u8;
+    }
+
+    // IV: 404142434445464748494a4b4c4d4e4f5051525354555658
+    let nonce = XNonce::new([
+        0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e,
+        0x4f, 0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x58,
+    ]);
+
+    let ciphertext = vec![
+        0x7d, 0x0a, 0x2e, 0x6b, 0x7f, 0x7c, 0x65, 0xa2, 0x36, 0x54, 0x26, 0x30, 0x29, 0x4e, 0x06,
+        0x3b, 0x7a, 0xb9, 0xb5, 0x55, 0xa5, 0xd5, 0x14, 0x9a, 0xa2, 0x1e, 0x4a, 0xe1, 0xe4, 0xfb,
+        0xce, 0x87, 0xec, 0xc8, 0xe0, 0x8a, 0x8b, 0x5e, 0x35, 0x0a, 0xbe, 0x62, 0x2b, 0x2f, 0xfa,
+        0x61, 0x7b, 0x20, 0x2c, 0xfa, 0xd7, 0x20, 0x32, 0xa3, 0x03, 0x7e, 0x76, 0xff, 0xdc, 0xdc,
+        0x43, 0x76, 0xee, 0x05, 0x3a, 0x19, 0x0d, 0x7e, 0x46, 0xca, 0x1d, 0xe0, 0x41, 0x44, 0x85,
+        0x03, 0x81, 0xb9, 0xcb, 0x29, 0xf0, 0x51, 0x91, 0x53, 0x86, 0xb8, 0xa7, 0x10, 0xb8, 0xac,
+        0x4d, 0x02, 0x7b, 0x8b, 0x05, 0x0f, 0x7c, 0xba, 0x58, 0x54, 0xe0, 0x28, 0xd5, 0x64, 0xe4,
+        0x53, 0xb8, 0xa9, 0x68, 0x82, 0x41, 0x73, 0xfc, 0x16, 0x48, 0x8b, 0x89, 0x70, 0xca, 0xc8,
+        0x28, 0xf1, 0x1a, 0xe5, 0x3c, 0xab, 0xd2, 0x01, 0x12, 0xf8, 0x71, 0x07, 0xdf, 0x24, 0xee,
+        0x61, 0x83, 0xd2, 0x27, 0x4f, 0xe4, 0xc8, 0xb1, 0x48, 0x55, 0x34, 0xef, 0x2c, 0x5f, 0xbc,
+        0x1e, 0xc2, 0x4b, 0xfc, 0x36, 0x63, 0xef, 0xaa, 0x08, 0xbc, 0x04, 0x7d, 0x29, 0xd2, 0x50,
+        0x43, 0x53, 0x2d, 0xb8, 0x39, 0x1a, 0x8a, 0x3d, 0x77, 0x6b, 0xf4, 0x37, 0x2a, 0x69, 0x55,
+        0x82, 0x7c, 0xcb, 0x0c, 0xdd, 0x4a, 0xf4, 0x03, 0xa7, 0xce, 0x4c, 0x63, 0xd5, 0x95, 0xc7,
+        0x5a, 0x43, 0xe0, 0x45, 0xf0, 0xcc, 0xe1, 0xf2, 0x9c, 0x8b, 0x93, 0xbd, 0x65, 0xaf, 0xc5,
+        0x97, 0x49, 0x22, 0xf2, 0x14, 0xa4, 0x0b, 0x7c, 0x40, 0x2c, 0xdb, 0x91, 0xae, 0x73, 0xc0,
+        0xb6, 0x36, 0x15, 0xcd, 0xad, 0x04, 0x80, 0x68, 0x0f, 0x16, 0x51, 0x5a, 0x7a, 0xce, 0x9d,
+        0x39, 0x23, 0x64, 0x64, 0x32, 0x8a, 0x37, 0x74, 0x3f, 0xfc, 0x28, 0xf4, 0xdd, 0xb3, 0x24,
+        0xf4, 0xd0, 0xf5, 0xbb, 0xdc, 0x27, 0x0c, 0x65, 0xb1, 0x74, 0x9a, 0x6e, 0xff, 0xf1, 0xfb,
+        0xaa, 0x09, 0x53, 0x61, 0x75, 0xcc, 0xd2, 0x9f, 0xb9, 0xe6, 0x05, 0x7b, 0x30, 0x73, 0x20,
+        0xd3, 0x16, 0x83, 0x8a, 0x9c, 0x71, 0xf7, 0x0b, 0x5b, 0x59, 0x07, 0xa6, 0x6f, 0x7e, 0xa4,
+        0x9a, 0xad, 0xc4, 0x09,
+    ];
+
+    (Key::new(key), nonce, ciphertext)
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_xchacha20_encrypt() {
+    let (key, nonce, ciphertext) = dhole_vector();
+    let cipher = XChaCha20::new(&key, &nonce);
+
+    assert_eq!(cipher.encrypt(1, DHOLE).unwrap(), ciphertext);
+    assert_eq!(cipher.decrypt(1, &ciphertext).unwrap(), DHOLE.to_vec());
+}
+
+#[test]
+fn test_xchacha20_apply_keystream() {
+    let (key, nonce, ciphertext) = dhole_vector();
+    let mut cipher = XChaCha20::new(&key, &nonce);
+
+    cipher.seek(64).unwrap();
+    let mut buf = DHOLE.to_vec();
+    for piece in buf.chunks_mut(7) {
+        cipher.apply_keystream(piece).unwrap();
+    }
+
+    assert_eq!(buf, ciphertext);
+    assert_eq!(cipher.current_pos(), 64 + DHOLE.len() as u64);
+}
+
+// Reduced-round test vectors from draft-strombergson-chacha-test-vectors.
+// The draft uses a 64-bit IV with a zero 64-bit block counter, which is the
+// same state as a 96-bit nonce of four zero bytes followed by the IV.
+
+#[cfg(test)]
+fn key_stream<const ROUNDS: usize>(key: [u8; 32], iv: [u8; 8]) -> Vec<u8> {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&iv);
+
+    let mut cipher = ChaCha::<ROUNDS>::new(&Key::new(key), &Nonce::new(nonce));
+    let mut buf = vec![0u8; 128];
+    cipher.apply_keystream(&mut buf).unwrap();
+    buf
+}
+
+#[cfg(test)]
+fn tc8_key_iv() -> ([u8; 32], [u8; 8]) {
+    // Key: c46ec1b18ce8a878725a37e780dfb7351f68ed2e194c79fbc6aebee1a667975d
+    // IV:  1ada31d5cf688221
+    let key = [
+        0xc4, 0x6e, 0xc1, 0xb1, 0x8c, 0xe8, 0xa8, 0x78, 0x72, 0x5a, 0x37, 0xe7, 0x80, 0xdf, 0xb7,
+        0x35, 0x1f, 0x68, 0xed, 0x2e, 0x19, 0x4c, 0x79, 0xfb, 0xc6, 0xae, 0xbe, 0xe1, 0xa6, 0x67,
+        0x97, 0x5d,
+    ];
+    let iv = [0x1a, 0xda, 0x31, 0xd5, 0xcf, 0x68, 0x82, 0x21];
+    (key, iv)
+}
+
+#[test]
+fn test_chacha8_tc1() {
+    // TC1: All zero key and IV.
+    let expected = vec![
+        0x3e, 0x00, 0xef, 0x2f, 0x89, 0x5f, 0x40, 0xd6, 0x7f, 0x5b, 0xb8, 0xe8, 0x1f, 0x09, 0xa5,
+        0xa1, 0x2c, 0x84, 0x0e, 0xc3, 0xce, 0x9a, 0x7f, 0x3b, 0x18, 0x1b, 0xe1, 0x88, 0xef, 0x71,
+        0x1a, 0x1e, 0x98, 0x4c, 0xe1, 0x72, 0xb9, 0x21, 0x6f, 0x41, 0x9f, 0x44, 0x53, 0x67, 0x45,
+        0x6d, 0x56, 0x19, 0x31, 0x4a, 0x42, 0xa3, 0xda, 0x86, 0xb0, 0x01, 0x38, 0x7b, 0xfd, 0xb8,
+        0x0e, 0x0c, 0xfe, 0x42, 0xd2, 0xae, 0xfa, 0x0d, 0xea, 0xa5, 0xc1, 0x51, 0xbf, 0x0a, 0xdb,
+        0x6c, 0x01, 0xf2, 0xa5, 0xad, 0xc0, 0xfd, 0x58, 0x12, 0x59, 0xf9, 0xa2, 0xaa, 0xdc, 0xf2,
+        0x0f, 0x8f, 0xd5, 0x66, 0xa2, 0x6b, 0x50, 0x32, 0xec, 0x38, 0xbb, 0xc5, 0xda, 0x98, 0xee,
+        0x0c, 0x6f, 0x56, 0x8b, 0x87, 0x2a, 0x65, 0xa0, 0x8a, 0xbf, 0x25, 0x1d, 0xeb, 0x21, 0xbb,
+        0x4b, 0x56, 0xe5, 0xd8, 0x82, 0x1e, 0x68, 0xaa,
+    ];
+
+    assert_eq!(key_stream::<8>([0; 32], [0; 8]), expected);
+}
+
+#[test]
+fn test_chacha8_tc8() {
+    // TC8: Random key and IV.
+    let (key, iv) = tc8_key_iv();
+    let expected = vec![
+        0x83, 0x87, 0x51, 0xb4, 0x2d, 0x8d, 0xdd, 0x8a, 0x3d, 0x77, 0xf4, 0x88, 0x25, 0xa2, 0xba,
+        0x75, 0x2c, 0xf4, 0x04, 0x7c, 0xb3, 0x08, 0xa5, 0x97, 0x8e, 0xf2, 0x74, 0x97, 0x3b, 0xe3,
+        0x74, 0xc9, 0x6a, 0xd8, 0x48, 0x06, 0x58, 0x71, 0x41, 0x7b, 0x08, 0xf0, 0x34, 0xe6, 0x81,
+        0xfe, 0x46, 0xa9, 0x3f, 0x7d, 0x5c, 0x61, 0xd1, 0x30, 0x66, 0x14, 0xd4, 0xaa, 0xf2, 0x57,
+        0xa7, 0xcf, 0xf0, 0x8b, 0x16, 0xf2, 0xfd, 0xa1, 0x70, 0xcc, 0x18, 0xa4, 0xb5, 0x8a, 0x26,
+        0x67, 0xed, 0x96, 0x27, 0x74, 0xaf, 0x79, 0x2a, 0x6e, 0x7f, 0x3c, 0x77, 0x99, 0x25, 0x40,
+        0x71, 0x1a, 0x7a, 0x13, 0x6d, 0x7e, 0x8a, 0x2f, 0x8d, 0x3f, 0x93, 0x81, 0x67, 0x09, 0xd4,
+        0x5a, 0x3f, 0xa5, 0xf8, 0xce, 0x72, 0xfd, 0xe1, 0x5b, 0xe7, 0xb8, 0x41, 0xac, 0xba, 0x3a,
+        0x2a, 0xbd, 0x55, 0x72, 0x28, 0xd9, 0xfe, 0x4f,
+    ];
+
+    assert_eq!(key_stream::<8>(key, iv), expected);
+}
+
+#[test]
+fn test_chacha12_tc1() {
+    // TC1: All zero key and IV.
+    let expected = vec![
+        0x9b, 0xf4, 0x9a, 0x6a, 0x07, 0x55, 0xf9, 0x53, 0x81, 0x1f, 0xce, 0x12, 0x5f, 0x26, 0x83,
+        0xd5, 0x04, 0x29, 0xc3, 0xbb, 0x49, 0xe0, 0x74, 0x14, 0x7e, 0x00, 0x89, 0xa5, 0x2e, 0xae,
+        0x15, 0x5f, 0x05, 0x64, 0xf8, 0x79, 0xd2, 0x7a, 0xe3, 0xc0, 0x2c, 0xe8, 0x28, 0x34, 0xac,
+        0xfa, 0x8c, 0x79, 0x3a, 0x62, 0x9f, 0x2c, 0xa0, 0xde, 0x69, 0x19, 0x61, 0x0b, 0xe8, 0x2f,
+        0x41, 0x13, 0x26, 0xbe, 0x0b, 0xd5, 0x88, 0x41, 0x20, 0x3e, 0x74, 0xfe, 0x86, 0xfc, 0x71,
+        0x33, 0x8c, 0xe0, 0x17, 0x3d, 0xc6, 0x28, 0xeb, 0xb7, 0x19, 0xbd, 0xcb, 0xcc, 0x15, 0x15,
+        0x85, 0x21, 0x4c, 0xc0, 0x89, 0xb4, 0x42, 0x25, 0x8d, 0xcd, 0xa1, 0x4c, 0xf1, 0x11, 0xc6,
+        0x02, 0xb8, 0x97, 0x1b, 0x8c, 0xc8, 0x43, 0xe9, 0x1e, 0x46, 0xca, 0x90, 0x51, 0x51, 0xc0,
+        0x27, 0x44, 0xa6, 0xb0, 0x17, 0xe6, 0x93, 0x16,
+    ];
+
+    assert_eq!(key_stream::<12>([0; 32], [0; 8]), expected);
+}
+
+#[test]
+fn test_chacha12_tc8() {
+    // TC8: Random key and IV.
+    let (key, iv) = tc8_key_iv();
+    let expected = vec![
+        0x14, 0x82, 0x07, 0x27, 0x84, 0xbc, 0x6d, 0x06, 0xb4, 0xe7, 0x3b, 0xdc, 0x11, 0x8b, 0xc0,
+        0x10, 0x3c, 0x79, 0x76, 0x78, 0x6c, 0xa9, 0x18, 0xe0, 0x69, 0x86, 0xaa, 0x25, 0x1f, 0x7e,
+        0x9c, 0xc1, 0xb2, 0x74, 0x9a, 0x0a, 0x16, 0xee, 0x83, 0xb4, 0x24, 0x2d, 0x2e, 0x99, 0xb0,
+        0x8d, 0x7c, 0x20, 0x09, 0x2b, 0x80, 0xbc, 0x46, 0x6c, 0x87, 0x28, 0x3b, 0x61, 0xb1, 0xb3,
+        0x9d, 0x0f, 0xfb, 0xab, 0xd9, 0x4b, 0x11, 0x6b, 0xc1, 0xeb, 0xdb, 0x32, 0x9b, 0x9e, 0x4f,
+        0x62, 0x0d, 0xb6, 0x95, 0x54, 0x4a, 0x8e, 0x3d, 0x9b, 0x68, 0x47, 0x3d, 0x0c, 0x97, 0x5a,
+        0x46, 0xad, 0x96, 0x6e, 0xd6, 0x31, 0xe4, 0x2a, 0xff, 0x53, 0x0a, 0xd5, 0xea, 0xc7, 0xd8,
+        0x04, 0x7a, 0xdf, 0xa1, 0xe5, 0x11, 0x3c, 0x91, 0xf3, 0xe3, 0xb8, 0x83, 0xf1, 0xd1, 0x89,
+        0xac, 0x1c, 0x8f, 0xe0, 0x7b, 0xa5, 0xa4, 0x2b,
+    ];
+
+    assert_eq!(key_stream::<12>(key, iv), expected);
+}
+
+#[test]
+fn test_chacha20_tc1() {
+    // TC1: All zero key and IV.
+    let expected = vec![
+        0x76, 0xb8, 0xe0, 0xad, 0xa0, 0xf1, 0x3d, 0x90, 0x40, 0x5d, 0x6a, 0xe5, 0x53, 0x86, 0xbd,
+        0x28, 0xbd, 0xd2, 0x19, 0xb8, 0xa0, 0x8d, 0xed, 0x1a, 0xa8, 0x36, 0xef, 0xcc, 0x8b, 0x77,
+        0x0d, 0xc7, 0xda, 0x41, 0x59, 0x7c, 0x51, 0x57, 0x48, 0x8d, 0x77, 0x24, 0xe0, 0x3f, 0xb8,
+        0xd8, 0x4a, 0x37, 0x6a, 0x43, 0xb8, 0xf4, 0x15, 0x18, 0xa1, 0x1c, 0xc3, 0x87, 0xb6, 0x69,
+        0xb2, 0xee, 0x65, 0x86, 0x9f, 0x07, 0xe7, 0xbe, 0x55, 0x51, 0x38, 0x7a, 0x98, 0xba, 0x97,
+        0x7c, 0x73, 0x2d, 0x08, 0x0d, 0xcb, 0x0f, 0x29, 0xa0, 0x48, 0xe3, 0x65, 0x69, 0x12, 0xc6,
+        0x53, 0x3e, 0x32, 0xee, 0x7a, 0xed, 0x29, 0xb7, 0x21, 0x76, 0x9c, 0xe6, 0x4e, 0x43, 0xd5,
+        0x71, 0x33, 0xb0, 0x74, 0xd8, 0x39, 0xd5, 0x31, 0xed, 0x1f, 0x28, 0x51, 0x0a, 0xfb, 0x45,
+        0xac, 0xe1, 0x0a, 0x1f, 0x4b, 0x79, 0x4d, 0x6f,
+    ];
+
+    assert_eq!(key_stream::<20>([0; 32], [0; 8]), expected);
+}
+
+#[test]
+fn test_chacha20_tc8() {
+    // TC8: Random key and IV.
+    let (key, iv) = tc8_key_iv();
+    let expected = vec![
+        0xf6, 0x3a, 0x89, 0xb7, 0x5c, 0x22, 0x71, 0xf9, 0x36, 0x88, 0x16, 0x54, 0x2b, 0xa5, 0x2f,
+        0x06, 0xed, 0x49, 0x24, 0x17, 0x92, 0x30, 0x2b, 0x00, 0xb5, 0xe8, 0xf8, 0x0a, 0xe9, 0xa4,
+        0x73, 0xaf, 0xc2, 0x5b, 0x21, 0x8f, 0x51, 0x9a, 0xf0, 0xfd, 0xd4, 0x06, 0x36, 0x2e, 0x8d,
+        0x69, 0xde, 0x7f, 0x54, 0xc6, 0x04, 0xa6, 0xe0, 0x0f, 0x35, 0x3f, 0x11, 0x0f, 0x77, 0x1b,
+        0xdc, 0xa8, 0xab, 0x92, 0xe5, 0xfb, 0xc3, 0x4e, 0x60, 0xa1, 0xd9, 0xa9, 0xdb, 0x17, 0x34,
+        0x5b, 0x0a, 0x40, 0x27, 0x36, 0x85, 0x3b, 0xf9, 0x10, 0xb0, 0x60, 0xbd, 0xf1, 0xf8, 0x97,
+        0xb6, 0x29, 0x0f, 0x01, 0xd1, 0x38, 0xae, 0x2c, 0x4c, 0x90, 0x22, 0x5b, 0xa9, 0xea, 0x14,
+        0xd5, 0x18, 0xf5, 0x59, 0x29, 0xde, 0xa0, 0x98, 0xca, 0x7a, 0x6c, 0xcf, 0xe6, 0x12, 0x27,
+        0x05, 0x3c, 0x84, 0xe4, 0x9a, 0x4a, 0x33, 0x32,
+    ];
+
+    assert_eq!(key_stream::<20>(key, iv), expected);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_reduced_rounds_differ() {
+    let nonce = Nonce::new([0; 12]);
+    let full = ChaCha20::new(&rfc_key(), &nonce)
+        .encrypt(0, &[0; 64])
+        .unwrap();
+
+    assert_ne!(
+        ChaCha12::new(&rfc_key(), &nonce)
+            .encrypt(0, &[0; 64])
+            .unwrap(),
+        full
+    );
+    assert_ne!(
+        ChaCha8::new(&rfc_key(), &nonce)
+            .encrypt(0, &[0; 64])
+            .unwrap(),
+        full
+    );
+}
+
+// The same draft-strombergson vectors through the original 64-bit nonce
+// layout, which is the layout the draft was written for.
+
+#[test]
+fn test_chacha20_legacy_tc1() {
+    let mut cipher = ChaCha20Legacy::new(&Key::new([0; 32]), &LegacyNonce::new([0; 8]));
+    let mut buf = vec![0u8; 128];
+    cipher.apply_keystream(&mut buf).unwrap();
+
+    assert_eq!(buf, key_stream::<20>([0; 32], [0; 8]));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_chacha20_legacy_tc8() {
+    let (key, iv) = tc8_key_iv();
+    let expected = vec![
+        0xf6, 0x3a, 0x89, 0xb7, 0x5c, 0x22, 0x71, 0xf9, 0x36, 0x88, 0x16, 0x54, 0x2b, 0xa5, 0x2f,
+        0x06, 0xed, 0x49, 0x24, 0x17, 0x92, 0x30, 0x2b, 0x00, 0xb5, 0xe8, 0xf8, 0x0a, 0xe9, 0xa4,
+        0x73, 0xaf, 0xc2, 0x5b, 0x21, 0x8f, 0x51, 0x9a, 0xf0, 0xfd, 0xd4, 0x06, 0x36, 0x2e, 0x8d,
+        0x69, 0xde, 0x7f, 0x54, 0xc6, 0x04, 0xa6, 0xe0, 0x0f, 0x35, 0x3f, 0x11, 0x0f, 0x77, 0x1b,
+        0xdc, 0xa8, 0xab, 0x92, 0xe5, 0xfb, 0xc3, 0x4e, 0x60, 0xa1, 0xd9, 0xa9, 0xdb, 0x17, 0x34,
+        0x5b, 0x0a, 0x40, 0x27, 0x36, 0x85, 0x3b, 0xf9, 0x10, 0xb0, 0x60, 0xbd, 0xf1, 0xf8, 0x97,
+        0xb6, 0x29, 0x0f, 0x01, 0xd1, 0x38, 0xae, 0x2c, 0x4c, 0x90, 0x22, 0x5b, 0xa9, 0xea, 0x14,
+        0xd5, 0x18, 0xf5, 0x59, 0x29, 0xde, 0xa0, 0x98, 0xca, 0x7a, 0x6c, 0xcf, 0xe6, 0x12, 0x27,
+        0x05, 0x3c, 0x84, 0xe4, 0x9a, 0x4a, 0x33, 0x32,
+    ];
+
+    let cipher = ChaCha20Legacy::new(&Key::new(key), &LegacyNonce::new(iv));
+    assert_eq!(cipher.encrypt(0, &[0; 128]).unwrap(), expected);
+
+    let mut cipher = ChaCha20Legacy::new(&Key::new(key), &LegacyNonce::new(iv));
+    let mut buf = vec![0u8; 128];
+    for piece in buf.chunks_mut(5) {
+        cipher.apply_keystream(piece).unwrap();
+    }
+    assert_eq!(buf, expected);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_chacha20_legacy_past_256_gib() {
+    // Crossing block 2^32 carries into word 13 instead of running out of
+    // key stream as the 32-bit RFC counter would.
+    let (key, iv) = tc8_key_iv();
+    let mut cipher = ChaCha20Legacy::new(&Key::new(key), &LegacyNonce::new(iv));
+    let pos = (1u128 << 32) * 64 - 32;
+    cipher.seek(pos).unwrap();
+
+    let mut buf = [0u8; 64];
+    cipher.apply_keystream(&mut buf).unwrap();
+    assert_eq!(cipher.current_pos(), pos + 64);
+
+    let mut expected = [0u8; 128];
+    for (i, block) in expected.chunks_exact_mut(64).enumerate() {
+        let state = init_state_legacy(&key, (1 << 32) - 1 + i as u64, &iv);
+        let mut out = [0u8; 64];
+        keystream_block::<20>(&state, &mut out);
+        block.copy_from_slice(&out);
+    }
+    assert_eq!(buf[..], expected[32..96]);
+
+    let cipher = ChaCha20Legacy::new(&Key::new(key), &LegacyNonce::new(iv));
+    assert_eq!(
+        cipher.encrypt((1 << 32) - 1, &[0; 128]).unwrap(),
+        expected.to_vec()
+    );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_chacha20_legacy_past_end() {
+    let mut cipher = ChaCha20Legacy::new(&Key::new([0; 32]), &LegacyNonce::new([0; 8]));
+    cipher.seek(LEGACY_KEYSTREAM_LEN - 1).unwrap();
+    assert_eq!(
+        cipher.apply_keystream(&mut [0; 2]),
+        Err(ChaChaError::CounterExhausted)
+    );
+    assert_eq!(
+        cipher.encrypt(u64::MAX, &[0; 65]),
+        Err(ChaChaError::CounterExhausted)
+    );
+}
@@ -0,0 +1,169 @@
+use crate::error::ChaChaError;
+use crate::wipe::wipe;
+use alloc::string::String;
+#[cfg(test)]
+use alloc::vec;
+use alloc::vec::Vec;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Decodes a hex string, upper or lower case, with no separators.  Errors
+// carry the offset of the offending character; an odd-length string is
+// reported at its end, where the missing digit would be.
+pub(crate) fn decode_hex(s: &str) -> Result<Vec<u8>, ChaChaError> {
+    let bytes = s.as_bytes();
+    if !bytes.len().is_multiple_of(2) {
+        return Err(ChaChaError::InvalidHex(bytes.len()));
+    }
+
+    let digit = |i: usize| {
+        (bytes[i] as char)
+            .to_digit(16)
+            .ok_or(ChaChaError::InvalidHex(i))
+    };
+
+    let mut out = Vec::with_capacity(bytes.len() / 2);
+    let result = (0..bytes.len()).step_by(2).try_for_each(|i| {
+        out.push((digit(i)? << 4 | digit(i + 1)?) as u8);
+        Ok(())
+    });
+
+    finish(out, result)
+}
+
+// Decodes standard base64 (RFC 4648 section 4).  The trailing `=` padding
+// may be left out, but if present it must be complete.  Unused bits in the
+// last character must be zero so that every value has one encoding.
+pub(crate) fn decode_base64(s: &str) -> Result<Vec<u8>, ChaChaError> {
+    let bytes = s.as_bytes();
+    let data_len = bytes.iter().rposition(|&c| c != b'=').map_or(0, |i| i + 1);
+    let padding = bytes.len() - data_len;
+
+    if data_len % 4 == 1 {
+        return Err(ChaChaError::InvalidBase64(data_len - 1));
+    }
+    if padding > 0 && (padding > 2 || !bytes.len().is_multiple_of(4)) {
+        return Err(ChaChaError::InvalidBase64(data_len));
+    }
+
+    let mut out = Vec::with_capacity(data_len * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    let result = bytes[..data_len]
+        .iter()
+        .enumerate()
+        .try_for_each(|(i, &c)| {
+            acc = acc << 6 | base64_value(c).ok_or(ChaChaError::InvalidBase64(i))?;
+            bits += 6;
+            if bits >= 8 {
+                bits -= 8;
+                out.push((acc >> bits) as u8);
+                acc &= (1 << bits) - 1;
+            }
+            Ok(())
+        });
+
+    let result = result.and_then(|()| match acc {
+        0 => Ok(()),
+        _ => Err(ChaChaError::InvalidBase64(data_len - 1)),
+    });
+    finish(out, result)
+}
+
+// Appends `data` to `out` as standard, padded base64 (RFC 4648 section 4).
+// Encoding in place lets a key go straight into a buffer the caller owns,
+// with no temporary copy left behind.
+pub(crate) fn encode_base64(data: &[u8], out: &mut String) {
+    out.reserve(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let mut group = [0u8; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let bits = u32::from(group[0]) << 16 | u32::from(group[1]) << 8 | u32::from(group[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+}
+
+// The decoded bytes may be a key, so a partly decoded buffer is wiped
+// rather than freed as it is when decoding fails.
+fn finish(mut out: Vec<u8>, result: Result<(), ChaChaError>) -> Result<Vec<u8>, ChaChaError> {
+    match result {
+        Ok(()) => Ok(out),
+        Err(e) => {
+            wipe(&mut out);
+            Err(e)
+        }
+    }
+}
+
+fn base64_value(c: u8) -> Option<u32> {
+    let v = match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => return None,
+    };
+
+    Some(u32::from(v))
+}
+
+#[test]
+fn test_decode_hex() {
+    assert_eq!(decode_hex(""), Ok(vec![]));
+    assert_eq!(decode_hex("00ff7Fa0"), Ok(vec![0x00, 0xff, 0x7f, 0xa0]));
+
+    assert_eq!(decode_hex("abc"), Err(ChaChaError::InvalidHex(3)));
+    assert_eq!(decode_hex("0g"), Err(ChaChaError::InvalidHex(1)));
+    assert_eq!(decode_hex("00 1"), Err(ChaChaError::InvalidHex(2)));
+    assert_eq!(decode_hex("0\u{e9}0"), Err(ChaChaError::InvalidHex(1)));
+}
+
+#[test]
+fn test_decode_base64() {
+    // RFC 4648 section 10.
+    let vectors: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+    for (plain, encoded) in vectors.iter() {
+        let mut out = String::from("x");
+        encode_base64(plain.as_bytes(), &mut out);
+        assert_eq!(out[1..], **encoded);
+        assert_eq!(decode_base64(encoded), Ok(plain.as_bytes().to_vec()));
+        let unpadded = encoded.trim_end_matches('=');
+        assert_eq!(decode_base64(unpadded), Ok(plain.as_bytes().to_vec()));
+    }
+
+    assert_eq!(decode_base64("+/+/"), Ok(vec![0xfb, 0xff, 0xbf]));
+    let mut out = String::new();
+    encode_base64(&[0xfb, 0xff, 0xbf], &mut out);
+    assert_eq!(out, "+/+/");
+}
+
+#[test]
+fn test_decode_base64_invalid() {
+    assert_eq!(decode_base64("Zm9v!"), Err(ChaChaError::InvalidBase64(4)));
+    assert_eq!(decode_base64("Zm9vY"), Err(ChaChaError::InvalidBase64(4)));
+    assert_eq!(decode_base64("Zg="), Err(ChaChaError::InvalidBase64(2)));
+    assert_eq!(
+        decode_base64("Zm9v===="),
+        Err(ChaChaError::InvalidBase64(4))
+    );
+    assert_eq!(decode_base64("Zm=v"), Err(ChaChaError::InvalidBase64(2)));
+    // "Zh==" has bits set past the end of the single byte it encodes.
+    assert_eq!(decode_base64("Zh=="), Err(ChaChaError::InvalidBase64(1)));
+}
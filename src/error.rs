@@ -0,0 +1,58 @@
+use core::fmt;
+
+/// Errors returned by the ciphers and the AEAD constructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChaChaError {
+    /// The operation would run the block counter past its last value and
+    /// reuse key stream.
+    CounterExhausted,
+    /// A key was not 32 bytes long.  Holds the length that was given.
+    InvalidKeyLength(usize),
+    /// A nonce was not the length the cipher uses.  Holds the length that
+    /// was given.
+    InvalidNonceLength(usize),
+    /// Source and destination buffers differ in length.
+    LengthMismatch,
+    /// An AEAD tag did not match the ciphertext and additional data.
+    AuthenticationFailed,
+    /// A hex string had a character that is not a hex digit, or an odd
+    /// number of digits.  Holds the byte offset of the problem.
+    InvalidHex(usize),
+    /// A base64 string had a character outside the alphabet, misplaced
+    /// padding or a truncated group.  Holds the byte offset of the problem.
+    InvalidBase64(usize),
+    /// A key file did not have the expected header or algorithm line, or
+    /// had extra content after the key.  Holds the line number, from 1, of
+    /// the problem.
+    InvalidKeyFile(usize),
+    /// scrypt cost parameters were out of range, or more output was asked
+    /// for than scrypt can produce.
+    InvalidScryptParams,
+}
+
+impl fmt::Display for ChaChaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChaChaError::CounterExhausted => write!(f, "block counter exhausted"),
+            ChaChaError::InvalidKeyLength(len) => {
+                write!(f, "invalid key length: expected 32 bytes, got {}", len)
+            }
+            ChaChaError::InvalidNonceLength(len) => {
+                write!(f, "invalid nonce length: got {} bytes", len)
+            }
+            ChaChaError::LengthMismatch => {
+                write!(f, "source and destination lengths differ")
+            }
+            ChaChaError::AuthenticationFailed => write!(f, "authentication failed"),
+            ChaChaError::InvalidHex(offset) => write!(f, "invalid hex at offset {}", offset),
+            ChaChaError::InvalidBase64(offset) => {
+                write!(f, "invalid base64 at offset {}", offset)
+            }
+            ChaChaError::InvalidKeyFile(line) => write!(f, "invalid key file at line {}", line),
+            ChaChaError::InvalidScryptParams => write!(f, "invalid scrypt parameters"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChaChaError {}
@@ -0,0 +1,292 @@
+// Key files shared between the `chacha20` tool and programs using the
+// library.  A key file is three lines of text:
+//
+//     rust-chacha20 key file v1
+//     algorithm: chacha20
+//     AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=
+//
+// The header names the format and its version, the algorithm line says what
+// the key is for, and the last line is the 32-byte key in padded base64.
+
+use crate::cipher::Key;
+use crate::encoding::encode_base64;
+use crate::error::ChaChaError;
+#[cfg(feature = "std")]
+use crate::wipe::wipe;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use rand_core::{OsRng, RngCore};
+#[cfg(feature = "std")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+#[cfg(all(feature = "std", unix))]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+const HEADER: &str = "rust-chacha20 key file v1";
+const ALGORITHM: &str = "algorithm: chacha20";
+
+// A key file is 91 bytes; this leaves room for line endings and trailing
+// whitespace, and stops a mistaken path to a large file or a device from
+// being read into memory.
+#[cfg(feature = "std")]
+const MAX_KEY_FILE_LEN: u64 = 128;
+
+impl Key {
+    /// Makes a new key from the operating system's random number generator.
+    ///
+    /// Panics if the operating system cannot provide random bytes.
+    #[cfg(feature = "std")]
+    pub fn generate() -> Key {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let key = Key::new(bytes);
+        wipe(&mut bytes);
+        key
+    }
+
+    /// Parses the text of a key file.  Trailing whitespace on each line and
+    /// blank lines after the key are ignored.
+    ///
+    /// Fails with `InvalidKeyFile` if the header or algorithm line is wrong
+    /// or missing, or anything follows the key, and with the errors of
+    /// `from_base64` if the key line is not a base64 key.
+    pub fn from_key_file(s: &str) -> Result<Key, ChaChaError> {
+        let mut lines = s.lines().map(str::trim_end);
+
+        if lines.next() != Some(HEADER) {
+            return Err(ChaChaError::InvalidKeyFile(1));
+        }
+        if lines.next() != Some(ALGORITHM) {
+            return Err(ChaChaError::InvalidKeyFile(2));
+        }
+        let key = Key::from_base64(lines.next().ok_or(ChaChaError::InvalidKeyFile(3))?)?;
+
+        match lines.position(|line| !line.is_empty()) {
+            Some(i) => Err(ChaChaError::InvalidKeyFile(4 + i)),
+            None => Ok(key),
+        }
+    }
+
+    /// Returns the text of a key file holding this key.  The text contains
+    /// the key, so take care where it goes, and `wipe` it when done.
+    pub fn to_key_file(&self) -> String {
+        // Sized exactly, so that no reallocation leaves a copy of the key.
+        let mut s = String::with_capacity(HEADER.len() + ALGORITHM.len() + 47);
+        s.push_str(HEADER);
+        s.push('\n');
+        s.push_str(ALGORITHM);
+        s.push('\n');
+        encode_base64(self.as_bytes(), &mut s);
+        s.push('\n');
+        s
+    }
+
+    /// Reads a key file.
+    ///
+    /// On Unix, fails with `PermissionDenied` if the file can be read by its
+    /// group or by others.  A file longer than 128 bytes gives `InvalidData`
+    /// without being read further, and one that does not parse gives
+    /// `InvalidData` wrapping the `ChaChaError`.
+    #[cfg(feature = "std")]
+    pub fn read_key_file<P: AsRef<Path>>(path: P) -> io::Result<Key> {
+        let file = File::open(path)?;
+        #[cfg(unix)]
+        {
+            if file.metadata()?.permissions().mode() & 0o044 != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "key file is readable by group or others",
+                ));
+            }
+        }
+
+        // Reserving room for one byte past the limit up front keeps the
+        // buffer from being reallocated and leaving copies behind.
+        let mut text = Vec::with_capacity(MAX_KEY_FILE_LEN as usize + 1);
+        let result = file.take(MAX_KEY_FILE_LEN + 1).read_to_end(&mut text);
+        let key = result.and_then(|len| {
+            if len as u64 > MAX_KEY_FILE_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "key file is too long",
+                ));
+            }
+            let text = core::str::from_utf8(&text)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "key file is not text"))?;
+            Key::from_key_file(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        });
+        wipe(&mut text);
+        key
+    }
+
+    /// Writes this key to a new key file at `path`, readable and writable
+    /// only by its owner on Unix.
+    ///
+    /// Fails with `AlreadyExists` rather than overwrite an existing file.
+    #[cfg(feature = "std")]
+    pub fn write_key_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options.open(path)?;
+        let mut text = self.to_key_file().into_bytes();
+        let result = file.write_all(&text).and_then(|()| file.sync_all());
+        wipe(&mut text);
+        result
+    }
+}
+
+#[cfg(test)]
+fn test_key() -> Key {
+    let mut bytes = [0u8; 32];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    Key::new(bytes)
+}
+
+#[cfg(all(test, feature = "std"))]
+fn temp_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(alloc::format!(
+        "rust-chacha20-{}-{}",
+        std::process::id(),
+        name
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn test_to_key_file() {
+    assert_eq!(
+        test_key().to_key_file(),
+        "rust-chacha20 key file v1\n\
+         algorithm: chacha20\n\
+         AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=\n"
+    );
+}
+
+#[test]
+fn test_from_key_file() {
+    let key = test_key();
+    assert!(Key::from_key_file(&key.to_key_file()) == Ok(key.clone()));
+
+    let crlf = "rust-chacha20 key file v1\r\nalgorithm: chacha20 \r\n\
+                AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8\r\n\r\n";
+    assert!(Key::from_key_file(crlf) == Ok(key));
+}
+
+#[test]
+fn test_from_key_file_invalid() {
+    let body = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+    let cases = [
+        ("", ChaChaError::InvalidKeyFile(1)),
+        (
+            "rust-chacha20 key file v2\n",
+            ChaChaError::InvalidKeyFile(1),
+        ),
+        (
+            "rust-chacha20 key file v1\nalgorithm: aes256\n",
+            ChaChaError::InvalidKeyFile(2),
+        ),
+        (
+            "rust-chacha20 key file v1\nalgorithm: chacha20\n",
+            ChaChaError::InvalidKeyFile(3),
+        ),
+        (
+            "rust-chacha20 key file v1\nalgorithm: chacha20\nAAEC\n",
+            ChaChaError::InvalidKeyLength(3),
+        ),
+        (
+            "rust-chacha20 key file v1\nalgorithm: chacha20\nAA!C\n",
+            ChaChaError::InvalidBase64(2),
+        ),
+    ];
+    for (text, err) in cases.iter() {
+        assert_eq!(Key::from_key_file(text).err(), Some(*err), "{:?}", text);
+    }
+
+    let extra = alloc::format!("{}\nalgorithm: chacha20\n{}\n\nmore\n", HEADER, body);
+    assert_eq!(
+        Key::from_key_file(&extra).err(),
+        Some(ChaChaError::InvalidKeyFile(5))
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_generate() {
+    assert!(Key::generate() != Key::generate());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_write_and_read_key_file() {
+    let path = temp_path("write-and-read");
+    let key = Key::generate();
+    key.write_key_file(&path).unwrap();
+    assert!(Key::read_key_file(&path).unwrap() == key);
+
+    // An existing file is never overwritten.
+    let err = Key::generate().write_key_file(&path).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    assert!(Key::read_key_file(&path).unwrap() == key);
+
+    #[cfg(unix)]
+    {
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(all(feature = "std", unix))]
+fn test_read_key_file_permissions() {
+    let path = temp_path("permissions");
+    test_key().write_key_file(&path).unwrap();
+
+    for &mode in [0o640, 0o604, 0o644].iter() {
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        let err = Key::read_key_file(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o400)).unwrap();
+    assert!(Key::read_key_file(&path).unwrap() == test_key());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_read_key_file_invalid() {
+    // Blank lines after the key are allowed, but only up to the length
+    // limit.
+    let padded = test_key().to_key_file() + &"\n".repeat(64);
+    let cases = [
+        ("not a key\n".as_bytes(), "invalid key file at line 1"),
+        (&[0xff, 0xfe, b'\n'][..], "key file is not text"),
+        (padded.as_bytes(), "key file is too long"),
+    ];
+
+    let path = temp_path("invalid");
+    for (contents, message) in cases.iter() {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        options.open(&path).unwrap().write_all(contents).unwrap();
+
+        let err = Key::read_key_file(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(alloc::format!("{}", err), *message);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1 +1,42 @@
+#![no_std]
+
+#[cfg(any(feature = "alloc", test))]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+mod aead;
+mod backend;
 mod chacha20;
+mod cipher;
+#[cfg(feature = "alloc")]
+mod encoding;
+mod error;
+#[cfg(feature = "alloc")]
+mod keyfile;
+mod poly1305;
+#[cfg(feature = "std")]
+mod random;
+mod rng;
+#[cfg(feature = "alloc")]
+mod scrypt;
+#[cfg(feature = "alloc")]
+mod sha256;
+mod wipe;
+
+pub use crate::aead::{constant_time_eq, ChaCha20Poly1305, XChaCha20Poly1305};
+#[cfg(feature = "alloc")]
+pub use crate::chacha20::chacha20_encrypt;
+pub use crate::chacha20::{chacha20_block, hchacha20, init_state, serialize_block};
+pub use crate::cipher::{
+    ChaCha, ChaCha12, ChaCha20, ChaCha20Legacy, ChaCha8, ChaChaLegacy, Key, LegacyNonce, Nonce,
+    XChaCha20, XNonce,
+};
+pub use crate::error::ChaChaError;
+pub use crate::poly1305::{poly1305, poly1305_key_gen, Poly1305};
+#[cfg(feature = "std")]
+pub use crate::random::{random_bytes, set_reseed_interval, SystemRng};
+pub use crate::rng::{ChaCha12Rng, ChaCha20Rng, ChaCha8Rng, ChaChaRng};
+#[cfg(feature = "alloc")]
+pub use crate::scrypt::{scrypt, ScryptParams};
+pub use crate::wipe::wipe;
@@ -1,516 +1,757 @@
-use std::mem;
-
-// 2.1.  The ChaCha Quarter Round
-
-//    The basic operation of the ChaCha algorithm is the quarter round.  It
-//    operates on four 32-bit unsigned integers, denoted a, b, c, and d.
-//    The operation is as follows (in C-like notation):
-
-//    1.  a += b; d ^= a; d <<<= 16;
-//    2.  c += d; b ^= c; b <<<= 12;
-//    3.  a += b; d ^= a; d <<<= 8;
-//    4.  c += d; b ^= c; b <<<= 7;
-fn quarter_round(mut a: u32, mut b: u32, mut c: u32, mut d: u32) -> (u32, u32, u32, u32) {
-    a = a.overflowing_add(b).0;
-    d ^= a;
-    d = d.rotate_left(16);
-    c = c.overflowing_add(d).0;
-    b ^= c;
-    b = b.rotate_left(12);
-    a = a.overflowing_add(b).0;
-    d ^= a;
-    d = d.rotate_left(8);
-    c = c.overflowing_add(d).0;
-    b ^= c;
-    b = b.rotate_left(7);
-    (a, b, c, d)
+// The `chacha20` command: encrypts or decrypts a file or stdin to stdout
+// with ChaCha20 (RFC 8439 section 2.4), and makes key files for it.
+// ChaCha20 alone does not authenticate anything, so a modified ciphertext
+// decrypts to garbage rather than failing.
+
+use rust_chacha20::{
+    constant_time_eq, random_bytes, scrypt, wipe, ChaCha20, Key, Nonce, ScryptParams,
+};
+use std::convert::TryFrom;
+use std::env;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::process;
+
+const USAGE: &str = "\
+usage: chacha20 encrypt|decrypt --nonce HEX [--key-file PATH] [--counter N] [FILE]
+       chacha20 encrypt|decrypt --passphrase [--counter N] [FILE]
+       chacha20 keygen PATH
+
+encrypt, decrypt: Encrypts or decrypts FILE, or stdin if FILE is missing or
+'-', to stdout.  Encryption and decryption are the same operation.
+
+keygen: Writes a new random key to the key file PATH, which must not exist.
+The file is created readable only by its owner.
+
+options:
+  --nonce HEX       the 96-bit nonce as 24 hex digits; never reuse one with
+                    the same key
+  --key-file PATH   read the key from a key file made by keygen, which must
+                    not be readable by group or others; without it the key,
+                    as 64 hex digits, is taken from the CHACHA20_KEY
+                    environment variable
+  --passphrase      derive the key from a passphrase with scrypt instead;
+                    the passphrase is taken from the CHACHA20_PASSPHRASE
+                    environment variable or asked for on the terminal.
+                    encrypt writes the salt and scrypt parameters in a
+                    header before the ciphertext, and decrypt reads them
+                    back and checks the passphrase
+  --counter N       the first block counter (default 1, as in RFC 8439)
+  -h, --help        print this help
+
+exit status:
+  0  success
+  1  reading the input or writing the output failed
+  2  invalid command line
+  3  invalid or missing key, nonce or passphrase";
+
+const KEY_ENV: &str = "CHACHA20_KEY";
+const PASSPHRASE_ENV: &str = "CHACHA20_PASSPHRASE";
+// Passphrases typed at the terminal are limited to this many bytes.
+const MAX_PASSPHRASE_LEN: usize = 1024;
+
+// The amount read and encrypted at a time.
+const CHUNK_LEN: usize = 64 * 1024;
+
+// Passphrase-encrypted output starts with a header: MAGIC, log2 N, r and p
+// (big-endian), the salt, and a check value that scrypt derives along with
+// the key so that decrypt can tell a wrong passphrase from a right one.
+// Every file has a fresh salt and so a fresh key, and the nonce is zero.
+const MAGIC: &[u8; 16] = b"ChaCha20-scrypt\x01";
+const SALT_LEN: usize = 16;
+const CHECK_LEN: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4 + 4 + SALT_LEN + CHECK_LEN;
+
+// decrypt refuses headers asking scrypt for more memory than this, or for
+// more than this many block mixes of 128 bytes, N * r * p: 64 times the
+// default parameters, or around a minute.
+const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+const MAX_SCRYPT_WORK: u64 = 1 << 24;
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Encrypt(Options),
+    Decrypt(Options),
+    Keygen(String),
 }
 
-macro_rules! macro_quarter_round {
-    ($a: expr, $b: expr, $c: expr, $d: expr) => {{
-        $a = $a.overflowing_add($b).0;
-        $d ^= $a;
-        $d = $d.rotate_left(16);
-        $c = $c.overflowing_add($d).0;
-        $b ^= $c;
-        $b = $b.rotate_left(12);
-        $a = $a.overflowing_add($b).0;
-        $d ^= $a;
-        $d = $d.rotate_left(8);
-        $c = $c.overflowing_add($d).0;
-        $b ^= $c;
-        $b = $b.rotate_left(7);
-    }};
+#[derive(Debug, PartialEq)]
+struct Options {
+    key_file: Option<String>,
+    nonce: Option<String>,
+    passphrase: bool,
+    counter: u32,
+    input: Option<String>,
 }
 
-// 2.1.1.  Test Vector for the ChaCha Quarter Round
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Key(String),
+    Io(String, io::Error),
+}
 
-//    For a test vector, we will use the same numbers as in the example,
-//    adding something random for c.
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Io(..) => 1,
+            CliError::Usage(_) => 2,
+            CliError::Key(_) => 3,
+        }
+    }
+}
 
-//    o  a = 0x11111111
-//    o  b = 0x01020304
-//    o  c = 0x9b8d6f43
-//    o  d = 0x01234567
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+            CliError::Key(message) => write!(f, "{}", message),
+            CliError::Io(what, err) => write!(f, "{}: {}", what, err),
+        }
+    }
+}
 
-//    After running a Quarter Round on these four numbers, we get these:
+#[derive(Debug, PartialEq)]
+struct Header {
+    params: ScryptParams,
+    salt: [u8; SALT_LEN],
+    check: [u8; CHECK_LEN],
+}
 
-//    o  a = 0xea2a92f4
-//    o  b = 0xcb1cf8ce
-//    o  c = 0x4581472e
-//    o  d = 0x5881c4bb
+impl Header {
+    fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        let (magic, rest) = bytes.split_at_mut(MAGIC.len());
+        magic.copy_from_slice(MAGIC);
+        rest[0] = self.params.log_n();
+        rest[1..5].copy_from_slice(&self.params.r().to_be_bytes());
+        rest[5..9].copy_from_slice(&self.params.p().to_be_bytes());
+        rest[9..9 + SALT_LEN].copy_from_slice(&self.salt);
+        rest[9 + SALT_LEN..].copy_from_slice(&self.check);
+        bytes
+    }
 
-#[test]
-fn test_quarter_round() {
-    assert_eq!(
-        quarter_round(0x11111111, 0x01020304, 0x9b8d6f43, 0x01234567),
-        (0xea2a92f4, 0xcb1cf8ce, 0x4581472e, 0x5881c4bb)
-    );
+    fn from_bytes(bytes: &[u8; HEADER_LEN]) -> Result<Header, CliError> {
+        let (magic, rest) = bytes.split_at(MAGIC.len());
+        if magic != MAGIC {
+            return Err(CliError::Key(
+                "input is not passphrase-encrypted".to_string(),
+            ));
+        }
 
-    let mut a: u32 = 0x11111111;
-    let mut b: u32 = 0x01020304;
-    let mut c: u32 = 0x9b8d6f43;
-    let mut d: u32 = 0x01234567;
-    macro_quarter_round!(a, b, c, d);
-    assert_eq!(0xea2a92f4, a);
-    assert_eq!(0xcb1cf8ce, b);
+        let word = |i: usize| u32::from_be_bytes([rest[i], rest[i + 1], rest[i + 2], rest[i + 3]]);
+        let params = ScryptParams::new(rest[0], word(1), word(5))
+            .map_err(|err| CliError::Key(format!("bad header: {}", err)))?;
+        let mut header = Header {
+            params,
+            salt: [0; SALT_LEN],
+            check: [0; CHECK_LEN],
+        };
+        header.salt.copy_from_slice(&rest[9..9 + SALT_LEN]);
+        header.check.copy_from_slice(&rest[9 + SALT_LEN..]);
+        Ok(header)
+    }
 }
 
-// 2.2.  A Quarter Round on the ChaCha State
-
-//    The ChaCha state does not have four integer numbers: it has 16.  So
-//    the quarter-round operation works on only four of them -- hence the
-//    name.  Each quarter round operates on four predetermined numbers in
-//    the ChaCha state.  We will denote by QUARTERROUND(x,y,z,w) a quarter-
-//    round operation on the numbers at indices x, y, z, and w of the
-//    ChaCha state when viewed as a vector.  For example, if we apply
-//    QUARTERROUND(1,5,9,13) to a state, this means running the quarter-
-//    round operation on the elements marked with an asterisk, while
-//    leaving the others alone:
-
-//       0  *a   2   3
-//       4  *b   6   7
-//       8  *c  10  11
-//      12  *d  14  15
-
-fn apply_quarter_round(x: usize, y: usize, z: usize, w: usize, words_16: Vec<u32>) -> Vec<u32> {
-    let old_a = words_16[x];
-    let old_b = words_16[y];
-    let old_c = words_16[z];
-    let old_d = words_16[w];
-
-    let (new_a, new_b, new_c, new_d) = quarter_round(old_a, old_b, old_c, old_d);
-
-    let mut ret = vec![0; 16];
-    ret[..16].clone_from_slice(&words_16[..16]);
-    ret[x] = new_a;
-    ret[y] = new_b;
-    ret[z] = new_c;
-    ret[w] = new_d;
-
-    ret
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    match run(&args) {
+        Ok(()) => {}
+        // The reader went away, as with `chacha20 ... | head`; nothing is
+        // left to say.
+        Err(CliError::Io(_, err)) if err.kind() == io::ErrorKind::BrokenPipe => process::exit(1),
+        Err(err) => {
+            eprintln!("chacha20: {}", err);
+            process::exit(err.exit_code());
+        }
+    }
 }
 
-// 2.2.1.  Test Vector for the Quarter Round on the ChaCha State
+fn run(args: &[String]) -> Result<(), CliError> {
+    let (decrypt, options) = match parse_args(args)? {
+        Command::Encrypt(options) => (false, options),
+        Command::Decrypt(options) => (true, options),
+        Command::Keygen(path) => {
+            return Key::generate()
+                .write_key_file(&path)
+                .map_err(|err| CliError::Io(format!("cannot write key file {}", path), err))
+        }
+    };
+
+    let (mut input, name): (Box<dyn Read>, &str) = match options.input.as_deref() {
+        None | Some("-") => (Box::new(io::stdin()), "stdin"),
+        Some(path) => {
+            let file = File::open(path)
+                .map_err(|err| CliError::Io(format!("cannot open {}", path), err))?;
+            (Box::new(file), path)
+        }
+    };
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+
+    let mut cipher = if !options.passphrase {
+        let key = read_key(&options)?;
+        let nonce = Nonce::from_hex(options.nonce.as_deref().unwrap_or_default())
+            .map_err(|err| CliError::Key(format!("bad nonce: {}", err)))?;
+        ChaCha20::new(&key, &nonce)
+    } else {
+        let passphrase = read_passphrase(!decrypt)?;
+        let cipher = if decrypt {
+            read_header(passphrase.as_bytes(), &mut input, name)
+        } else {
+            write_header(passphrase.as_bytes(), &ScryptParams::default(), &mut output)
+        };
+        wipe(&mut passphrase.into_bytes());
+        cipher?
+    };
+
+    cipher
+        .seek(u64::from(options.counter) * 64)
+        .map_err(|err| CliError::Usage(format!("bad counter: {}", err)))?;
+    stream(&mut cipher, &mut input, &mut output, name)
+}
 
-//    For a test vector, we will use a ChaCha state that was generated
-//    randomly:
+fn parse_args(args: &[String]) -> Result<Command, CliError> {
+    let mut args = args.iter();
+    let decrypt = match args.next().map(String::as_str) {
+        Some("encrypt") => false,
+        Some("decrypt") => true,
+        Some("keygen") => {
+            return match (args.next(), args.next()) {
+                (Some(path), None) if !path.starts_with('-') => Ok(Command::Keygen(path.clone())),
+                _ => Err(CliError::Usage(
+                    "keygen takes one key file path".to_string(),
+                )),
+            }
+        }
+        Some(other) => return Err(CliError::Usage(format!("unknown command '{}'", other))),
+        None => return Err(CliError::Usage("missing command".to_string())),
+    };
+
+    let mut key_file = None;
+    let mut nonce = None;
+    let mut passphrase = false;
+    let mut counter = 1;
+    let mut input = None;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| CliError::Usage(format!("{} needs a value", arg)))
+        };
+
+        match arg.as_str() {
+            "--key-file" => key_file = Some(value()?),
+            "--nonce" => nonce = Some(value()?),
+            "--passphrase" => passphrase = true,
+            "--counter" => {
+                let n = value()?;
+                counter = n
+                    .parse()
+                    .map_err(|_| CliError::Usage(format!("bad counter '{}'", n)))?;
+            }
+            "-" => input = Some(arg.clone()),
+            _ if arg.starts_with('-') => {
+                return Err(CliError::Usage(format!("unknown option '{}'", arg)))
+            }
+            _ if input.is_some() => {
+                return Err(CliError::Usage("more than one input file".to_string()))
+            }
+            _ => input = Some(arg.clone()),
+        }
+    }
 
-//    Sample ChaCha State
+    if passphrase && (nonce.is_some() || key_file.is_some()) {
+        return Err(CliError::Usage(
+            "--passphrase cannot be used with --nonce or --key-file".to_string(),
+        ));
+    }
+    if !passphrase && nonce.is_none() {
+        return Err(CliError::Usage("missing --nonce".to_string()));
+    }
 
-//        879531e0  c5ecf37d  516461b1  c9a62f8a
-//        44c20ef3  3390af7f  d9fc690b  2a5f714c
-//        53372767  b00a5631  974c541a  359e9963
-//        5c971061  3d631689  2098d9d6  91dbd320
+    let options = Options {
+        key_file,
+        nonce,
+        passphrase,
+        counter,
+        input,
+    };
+    Ok(if decrypt {
+        Command::Decrypt(options)
+    } else {
+        Command::Encrypt(options)
+    })
+}
 
-//    We will apply the QUARTERROUND(2,7,8,13) operation to this state.
-//    For obvious reasons, this one is part of what is called a "diagonal
-//    round":
+fn read_key(options: &Options) -> Result<Key, CliError> {
+    if let Some(path) = &options.key_file {
+        return Key::read_key_file(path)
+            .map_err(|err| CliError::Key(format!("cannot use key file {}: {}", path, err)));
+    }
 
-//    After applying QUARTERROUND(2,7,8,13)
+    let text = env::var(KEY_ENV)
+        .map_err(|_| CliError::Key(format!("no key: use --key-file or set {}", KEY_ENV)))?;
 
-//        879531e0  c5ecf37d *bdb886dc  c9a62f8a
-//        44c20ef3  3390af7f  d9fc690b *cfacafd2
-//       *e46bea80  b00a5631  974c541a  359e9963
-//        5c971061 *ccc07c79  2098d9d6  91dbd320
+    let key = Key::from_hex(text.trim());
+    // The library wipes its own copies of the key; this is the text it came
+    // from.
+    wipe(&mut text.into_bytes());
 
-//    Note that only the numbers in positions 2, 7, 8, and 13 changed.
+    key.map_err(|err| CliError::Key(format!("bad key: {}", err)))
+}
 
-#[test]
-fn test_apply_quarter_round() {
-    let input: Vec<u32> = vec![
-        0x879531e0, 0xc5ecf37d, 0x516461b1, 0xc9a62f8a, 0x44c20ef3, 0x3390af7f, 0xd9fc690b,
-        0x2a5f714c, 0x53372767, 0xb00a5631, 0x974c541a, 0x359e9963, 0x5c971061, 0x3d631689,
-        0x2098d9d6, 0x91dbd320,
-    ];
-
-    let output: Vec<u32> = vec![
-        0x879531e0, 0xc5ecf37d, 0xbdb886dc, 0xc9a62f8a, 0x44c20ef3, 0x3390af7f, 0xd9fc690b,
-        0xcfacafd2, 0xe46bea80, 0xb00a5631, 0x974c541a, 0x359e9963, 0x5c971061, 0xccc07c79,
-        0x2098d9d6, 0x91dbd320,
-    ];
-
-    let actual = apply_quarter_round(2, 7, 8, 13, input);
-
-    assert_eq!(output, actual);
+// Takes the passphrase from the environment, or else asks for it on the
+// terminal, twice if `confirm` is set.
+fn read_passphrase(confirm: bool) -> Result<String, CliError> {
+    let passphrase = match env::var(PASSPHRASE_ENV) {
+        Ok(passphrase) => passphrase,
+        Err(_) => prompt_passphrase(confirm)
+            .map_err(|err| CliError::Key(format!("cannot read passphrase: {}", err)))?,
+    };
+
+    if passphrase.is_empty() {
+        return Err(CliError::Key("empty passphrase".to_string()));
+    }
+    Ok(passphrase)
 }
 
-#[test]
-fn test_apply_quarter_round_2() {
-    let mut input: Vec<u32> = vec![
-        0x879531e0, 0xc5ecf37d, 0x516461b1, 0xc9a62f8a, 0x44c20ef3, 0x3390af7f, 0xd9fc690b,
-        0x2a5f714c, 0x53372767, 0xb00a5631, 0x974c541a, 0x359e9963, 0x5c971061, 0x3d631689,
-        0x2098d9d6, 0x91dbd320,
-    ];
-
-    let output: Vec<u32> = vec![
-        0x879531e0, 0xc5ecf37d, 0xbdb886dc, 0xc9a62f8a, 0x44c20ef3, 0x3390af7f, 0xd9fc690b,
-        0xcfacafd2, 0xe46bea80, 0xb00a5631, 0x974c541a, 0x359e9963, 0x5c971061, 0xccc07c79,
-        0x2098d9d6, 0x91dbd320,
-    ];
-
-    //let actual = apply_quarter_round(2, 7, 8, 13, input);
-    macro_quarter_round!(input[2], input[7], input[8], input[13]);
-
-    assert_eq!(output, input);
+// Reads passphrases from the terminal itself, since stdin may be the data.
+// Echo is turned off with `stty` where that is available.
+fn prompt_passphrase(confirm: bool) -> io::Result<String> {
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    let echo_off = stty(&tty, "-echo");
+
+    let ask = |prompt: &str| -> io::Result<String> {
+        (&tty).write_all(prompt.as_bytes())?;
+        let line = read_line(&tty)?;
+        match (&tty).write_all(b"\n") {
+            Ok(()) => Ok(line),
+            Err(err) => {
+                wipe(&mut line.into_bytes());
+                Err(err)
+            }
+        }
+    };
+    let result = ask("passphrase: ").and_then(|passphrase| {
+        if confirm {
+            let again = match ask("passphrase again: ") {
+                Ok(again) => again,
+                Err(err) => {
+                    wipe(&mut passphrase.into_bytes());
+                    return Err(err);
+                }
+            };
+            let matches = again == passphrase;
+            wipe(&mut again.into_bytes());
+            if !matches {
+                wipe(&mut passphrase.into_bytes());
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "passphrases do not match",
+                ));
+            }
+        }
+        Ok(passphrase)
+    });
+
+    if echo_off {
+        stty(&tty, "echo");
+    }
+    result
 }
 
-fn setup_key(key: Vec<u8>, counter: u32, nonce: Vec<u8>) -> Vec<u32> {
-    // The ChaCha20 state is initialized as follows:
-
-    let mut state: Vec<u32> = vec![0; 16];
-
-    // o  The first four words (0-3) are constants: 0x61707865, 0x3320646e,
-    //    0x79622d32, 0x6b206574.
-    state[0] = 0x6170_7865;
-    state[1] = 0x3320_646e;
-    state[2] = 0x7962_2d32;
-    state[3] = 0x6b20_6574;
-
-    // o  The next eight words (4-11) are taken from the 256-bit key by
-    //    reading the bytes in little-endian order, in 4-byte chunks.
-    for i in 0..8 {
-        let idx_state = i + 4;
-        let idx_key = 4 * i;
-
-        unsafe {
-            state[idx_state] = mem::transmute::<[u8; 4], u32>([
-                key[idx_key],
-                key[idx_key + 1],
-                key[idx_key + 2],
-                key[idx_key + 3],
-            ]);
+// Reads a line of the passphrase without its line ending.  Bytes are read
+// one at a time, as a BufReader would keep a copy of the passphrase in its
+// own buffer, into a line with room for any passphrase worth typing, so
+// that growing it leaves no copies behind either.  The line is wiped if it
+// is not returned.
+fn read_line<R: Read>(mut input: R) -> io::Result<String> {
+    let mut line = Vec::with_capacity(MAX_PASSPHRASE_LEN);
+    let mut byte = [0u8; 1];
+    let result = loop {
+        match input.read(&mut byte) {
+            Ok(0) => break Ok(()),
+            Ok(_) if byte[0] == b'\n' => break Ok(()),
+            Ok(_) if line.len() == MAX_PASSPHRASE_LEN => {
+                break Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "passphrase is too long",
+                ))
+            }
+            Ok(_) => line.push(byte[0]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => break Err(err),
         }
+    };
+    wipe(&mut byte);
+    if line.last() == Some(&b'\r') {
+        line.truncate(line.len() - 1);
     }
 
-    // o  Word 12 is a block counter.  Since each block is 64-byte, a 32-bit
-    //    word is enough for 256 gigabytes of data.
-    state[12] = counter;
-
-    // o  Words 13-15 are a nonce, which should not be repeated for the same
-    //    key.  The 13th word is the first 32 bits of the input nonce taken
-    //    as a little-endian integer, while the 15th word is the last 32
-    //    bits.
-    for i in 0..3 {
-        let idx_state = 13 + i;
-        let idx_nonce = 4 * i;
-
-        unsafe {
-            state[idx_state] = mem::transmute::<[u8; 4], u32>([
-                nonce[idx_nonce],
-                nonce[idx_nonce + 1],
-                nonce[idx_nonce + 2],
-                nonce[idx_nonce + 3],
-            ]);
+    match result {
+        Ok(()) => String::from_utf8(line).map_err(|err| {
+            wipe(&mut err.into_bytes());
+            io::Error::new(io::ErrorKind::InvalidData, "passphrase is not text")
+        }),
+        Err(err) => {
+            wipe(&mut line);
+            Err(err)
         }
     }
+}
 
-    state
+// Runs `stty setting` on the terminal and returns whether it worked.
+fn stty(tty: &File, setting: &str) -> bool {
+    tty.try_clone()
+        .and_then(|tty| {
+            process::Command::new("stty")
+                .arg(setting)
+                .stdin(tty)
+                .status()
+        })
+        .is_ok_and(|status| status.success())
 }
 
-#[test]
-fn test_setup_key() {
-    // o  Key = 00:01:02:03:04:05:06:07:08:09:0a:0b:0c:0d:0e:0f:10:11:12:13:
-    //    14:15:16:17:18:19:1a:1b:1c:1d:1e:1f.  The key is a sequence of
-    //    octets with no particular structure before we copy it into the
-    //    ChaCha state.
-    // o  Nonce = (00:00:00:09:00:00:00:4a:00:00:00:00)
-    // o  Block Count = 1.
-    let key: Vec<u8> = vec![
-        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
-        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
-        0x1e, 0x1f,
-    ];
-    let nonce: Vec<u8> = vec![
-        0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
-    ];
-    let block_count = 1;
-
-    let actual = setup_key(key, block_count, nonce);
-
-    // ChaCha state with the key setup.
-
-    // 61707865  3320646e  79622d32  6b206574
-    // 03020100  07060504  0b0a0908  0f0e0d0c
-    // 13121110  17161514  1b1a1918  1f1e1d1c
-    // 00000001  09000000  4a000000  00000000
-    let expected = vec![
-        0x61707865, 0x3320646e, 0x79622d32, 0x6b206574, 0x03020100, 0x07060504, 0x0b0a0908,
-        0x0f0e0d0c, 0x13121110, 0x17161514, 0x1b1a1918, 0x1f1e1d1c, 0x00000001, 0x09000000,
-        0x4a000000, 0x00000000,
-    ];
-
-    assert_eq!(actual, expected);
+// Derives the key and the check value for `header` from the passphrase.
+fn derive_key(passphrase: &[u8], header: &Header) -> Result<(Key, [u8; CHECK_LEN]), CliError> {
+    let mut derived = [0u8; 32 + CHECK_LEN];
+    scrypt(passphrase, &header.salt, &header.params, &mut derived)
+        .map_err(|err| CliError::Key(format!("cannot derive key: {}", err)))?;
+
+    let key = Key::try_from(&derived[..32]).unwrap();
+    let mut check = [0u8; CHECK_LEN];
+    check.copy_from_slice(&derived[32..]);
+    wipe(&mut derived);
+    Ok((key, check))
 }
 
-// 2.3.1.  The ChaCha20 Block Function in Pseudocode
-
-//    Note: This section and a few others contain pseudocode for the
-//    algorithm explained in a previous section.  Every effort was made for
-//    the pseudocode to accurately reflect the algorithm as described in
-//    the preceding section.  If a conflict is still present, the textual
-//    explanation and the test vectors are normative.
-
-//       inner_block (state):
-//          Qround(state, 0, 4, 8,12)
-//          Qround(state, 1, 5, 9,13)
-//          Qround(state, 2, 6,10,14)
-//          Qround(state, 3, 7,11,15)
-//          Qround(state, 0, 5,10,15)
-//          Qround(state, 1, 6,11,12)
-//          Qround(state, 2, 7, 8,13)
-//          Qround(state, 3, 4, 9,14)
-//          end
-
-//       chacha20_block(key, counter, nonce):
-//          state = constants | key | counter | nonce
-//          working_state = state
-//          for i=1 upto 10
-//             inner_block(working_state)
-//             end
-//          state += working_state
-//          return serialize(state)
-//          end
-
-fn block_function(key: Vec<u8>, counter: u32, nonce: Vec<u8>) -> Vec<u32> {
-    // The ChaCha20 state is initialized as follows:
-
-    let mut state = setup_key(key, counter, nonce);
-
-    let mut x = state.clone();
-    for _ in 1..=10 {
-        macro_quarter_round!(x[0], x[4], x[8], x[12]);
-        macro_quarter_round!(x[1], x[5], x[9], x[13]);
-        macro_quarter_round!(x[2], x[6], x[10], x[14]);
-        macro_quarter_round!(x[3], x[7], x[11], x[15]);
-        macro_quarter_round!(x[0], x[5], x[10], x[15]);
-        macro_quarter_round!(x[1], x[6], x[11], x[12]);
-        macro_quarter_round!(x[2], x[7], x[8], x[13]);
-        macro_quarter_round!(x[3], x[4], x[9], x[14]);
+// Derives a key from the passphrase with a new salt, writes the header to
+// `output` and returns the cipher for the data that follows.
+fn write_header<W: Write>(
+    passphrase: &[u8],
+    params: &ScryptParams,
+    output: &mut W,
+) -> Result<ChaCha20, CliError> {
+    let mut header = Header {
+        params: *params,
+        salt: [0; SALT_LEN],
+        check: [0; CHECK_LEN],
+    };
+    random_bytes(&mut header.salt);
+    let (key, check) = derive_key(passphrase, &header)?;
+    header.check = check;
+
+    output
+        .write_all(&header.to_bytes())
+        .map_err(|err| CliError::Io("cannot write output".to_string(), err))?;
+    Ok(ChaCha20::new(&key, &Nonce::new([0; 12])))
+}
+
+// Reads the header from `input`, checks the passphrase against it and
+// returns the cipher for the data that follows.
+fn read_header<R: Read>(
+    passphrase: &[u8],
+    input: &mut R,
+    name: &str,
+) -> Result<ChaCha20, CliError> {
+    let mut bytes = [0u8; HEADER_LEN];
+    input
+        .read_exact(&mut bytes)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => {
+                CliError::Key("input is not passphrase-encrypted".to_string())
+            }
+            _ => CliError::Io(format!("cannot read {}", name), err),
+        })?;
+
+    let header = Header::from_bytes(&bytes)?;
+    if header.params.memory() > MAX_SCRYPT_MEMORY {
+        return Err(CliError::Key(format!(
+            "header asks for {} MiB for scrypt, more than the {} MiB allowed",
+            header.params.memory() >> 20,
+            MAX_SCRYPT_MEMORY >> 20
+        )));
+    }
+    let params = &header.params;
+    let work = (u64::from(params.r()) * u64::from(params.p())) << params.log_n();
+    if work > MAX_SCRYPT_WORK {
+        return Err(CliError::Key(format!(
+            "header asks for {} block mixes for scrypt, more than the {} allowed",
+            work, MAX_SCRYPT_WORK
+        )));
     }
 
-    for i in 0..16 {
-        state[i] = state[i].overflowing_add(x[i]).0;
+    let (key, check) = derive_key(passphrase, &header)?;
+    if !constant_time_eq(&header.check, &check) {
+        return Err(CliError::Key("wrong passphrase".to_string()));
     }
+    Ok(ChaCha20::new(&key, &Nonce::new([0; 12])))
+}
 
-    state
+// Copies `input` to `output` through the cipher, a chunk at a time, so
+// inputs of any size use a fixed amount of memory.
+fn stream<R: Read, W: Write>(
+    cipher: &mut ChaCha20,
+    input: &mut R,
+    output: &mut W,
+    name: &str,
+) -> Result<(), CliError> {
+    let mut buffer = vec![0u8; CHUNK_LEN];
+    let result = stream_through(cipher, input, output, name, &mut buffer);
+    // The buffer last held a chunk of plaintext, on one side or the other.
+    wipe(&mut buffer);
+    result
 }
 
-fn serialized(arr32: Vec<u32>) -> Vec<u8> {
-    let mut serialized: Vec<u8> = vec![0; arr32.len() * 4];
-    for i in 0..16 {
-        unsafe {
-            let arr8 = mem::transmute::<u32, [u8; 4]>(arr32[i]);
-            serialized[i * 4] = arr8[0];
-            serialized[i * 4 + 1] = arr8[1];
-            serialized[i * 4 + 2] = arr8[2];
-            serialized[i * 4 + 3] = arr8[3];
-        }
+fn stream_through<R: Read, W: Write>(
+    cipher: &mut ChaCha20,
+    input: &mut R,
+    output: &mut W,
+    name: &str,
+    buffer: &mut [u8],
+) -> Result<(), CliError> {
+    loop {
+        let n = match input.read(buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(CliError::Io(format!("cannot read {}", name), err)),
+        };
+
+        cipher.apply_keystream(&mut buffer[..n]).map_err(|err| {
+            CliError::Io(
+                format!("cannot encrypt {}", name),
+                io::Error::new(io::ErrorKind::InvalidInput, err),
+            )
+        })?;
+        output
+            .write_all(&buffer[..n])
+            .map_err(|err| CliError::Io("cannot write output".to_string(), err))?;
     }
 
-    serialized
+    output
+        .flush()
+        .map_err(|err| CliError::Io("cannot write output".to_string(), err))
+}
+
+#[cfg(test)]
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(String::from).collect()
 }
 
 #[test]
-fn test_block_function() {
-    // o  Key = 00:01:02:03:04:05:06:07:08:09:0a:0b:0c:0d:0e:0f:10:11:12:13:
-    //    14:15:16:17:18:19:1a:1b:1c:1d:1e:1f.  The key is a sequence of
-    //    octets with no particular structure before we copy it into the
-    //    ChaCha state.
-    // o  Nonce = (00:00:00:09:00:00:00:4a:00:00:00:00)
-    // o  Block Count = 1.
-    let key: Vec<u8> = vec![
-        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
-        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
-        0x1e, 0x1f,
-    ];
-    let nonce: Vec<u8> = vec![
-        0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
-    ];
-    let block_count = 1;
-
-    let actual = block_function(key, block_count, nonce);
-
-    // ChaCha state after 20 rounds
-
-    // 837778ab  e238d763  a67ae21e  5950bb2f
-    // c4f2d0c7  fc62bb2f  8fa018fc  3f5ec7b7
-    // 335271c2  f29489f3  eabda8fc  82e46ebd
-    // d19c12b4  b04e16de  9e83d0cb  4e3c50a2
-
-    // ChaCha state at the end of the ChaCha20 operation
-
-    //     e4e7f110  15593bd1  1fdd0f50  c47120a3
-    //     c7f4d1c7  0368c033  9aaa2204  4e6cd4c3
-    //     466482d2  09aa9f07  05d7c214  a2028bd9
-    //     d19c12b5  b94e16de  e883d0cb  4e3c50a2
-    let expected_at_the_end = vec![
-        0xe4e7f110, 0x15593bd1, 0x1fdd0f50, 0xc47120a3, 0xc7f4d1c7, 0x0368c033, 0x9aaa2204,
-        0x4e6cd4c3, 0x466482d2, 0x09aa9f07, 0x05d7c214, 0xa2028bd9, 0xd19c12b5, 0xb94e16de,
-        0xe883d0cb, 0x4e3c50a2,
-    ];
-
-    // Serialized Block:
-    // 000  10 f1 e7 e4 d1 3b 59 15 50 0f dd 1f a3 20 71 c4  .....;Y.P.... q.
-    // 016  c7 d1 f4 c7 33 c0 68 03 04 22 aa 9a c3 d4 6c 4e  ....3.h.."....lN
-    // 032  d2 82 64 46 07 9f aa 09 14 c2 d7 05 d9 8b 02 a2  ..dF............
-    // 048  b5 12 9c d1 de 16 4e b9 cb d0 83 e8 a2 50 3c 4e  ......N......P<N
-    let expected = vec![
-        0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15, 0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20, 0x71,
-        0xc4, // end row1
-        0xc7, 0xd1, 0xf4, 0xc7, 0x33, 0xc0, 0x68, 0x03, 0x04, 0x22, 0xaa, 0x9a, 0xc3, 0xd4, 0x6c,
-        0x4e, // end row2
-        0xd2, 0x82, 0x64, 0x46, 0x07, 0x9f, 0xaa, 0x09, 0x14, 0xc2, 0xd7, 0x05, 0xd9, 0x8b, 0x02,
-        0xa2, // end row3
-        0xb5, 0x12, 0x9c, 0xd1, 0xde, 0x16, 0x4e, 0xb9, 0xcb, 0xd0, 0x83, 0xe8, 0xa2, 0x50, 0x3c,
-        0x4e, // end row4
-    ];
-
-    assert_eq!(actual, expected_at_the_end);
-    assert_eq!(serialized(actual), expected);
+fn test_parse_args() {
+    let command = parse_args(&args("encrypt --nonce 00 --key-file k --counter 7 in")).unwrap();
+    assert_eq!(
+        command,
+        Command::Encrypt(Options {
+            key_file: Some("k".to_string()),
+            nonce: Some("00".to_string()),
+            passphrase: false,
+            counter: 7,
+            input: Some("in".to_string()),
+        })
+    );
+
+    let options = match parse_args(&args("decrypt - --nonce 00")).unwrap() {
+        Command::Decrypt(options) => options,
+        command => panic!("{:?}", command),
+    };
+    assert_eq!(options.counter, 1);
+    assert_eq!(options.key_file, None);
+    assert_eq!(options.input, Some("-".to_string()));
+
+    for bad in [
+        "",
+        "frobnicate --nonce 00",
+        "encrypt",
+        "encrypt --nonce",
+        "encrypt --nonce 00 --counter x",
+        "encrypt --nonce 00 --verbose",
+        "encrypt --nonce 00 a b",
+        "keygen",
+        "keygen a b",
+        "keygen --nonce",
+        "encrypt --passphrase --nonce 00",
+        "decrypt --passphrase --key-file k",
+    ]
+    .iter()
+    {
+        let err = parse_args(&args(bad)).unwrap_err();
+        assert_eq!(err.exit_code(), 2, "{}", bad);
+    }
 }
 
-fn chacha20_encrypt(key: Vec<u8>, counter: u32, nonce: Vec<u8>, plaintext: Vec<u8>) -> Vec<u8> {
-    let mut encrypted_message = vec![0; plaintext.len()];
+#[test]
+fn test_parse_keygen() {
+    assert_eq!(
+        parse_args(&args("keygen k")).unwrap(),
+        Command::Keygen("k".to_string())
+    );
+}
 
-    for j in 0..(plaintext.len() / 64) {
-        let key_stream = serialized(block_function(
-            key.clone(),
-            counter + j as u32,
-            nonce.clone(),
-        ));
-        let block = &plaintext[j * 64..=(j * 64 + 63)];
+#[test]
+fn test_parse_passphrase() {
+    assert_eq!(
+        parse_args(&args("decrypt --passphrase in")).unwrap(),
+        Command::Decrypt(Options {
+            key_file: None,
+            nonce: None,
+            passphrase: true,
+            counter: 1,
+            input: Some("in".to_string()),
+        })
+    );
+}
 
-        for k in 0..64 {
-            encrypted_message[j * 64 + k] = block[k] ^ key_stream[k];
-        }
-    }
-    if plaintext.len() % 64 != 1 {
-        let j = plaintext.len() / 64;
-        let key_stream = serialized(block_function(
-            key.clone(),
-            counter + j as u32,
-            nonce.clone(),
-        ));
-        let block = &plaintext[j * 64..plaintext.len()];
+#[test]
+fn test_header() {
+    let header = Header {
+        params: ScryptParams::new(15, 8, 2).unwrap(),
+        salt: [0x5a; SALT_LEN],
+        check: [0xc3; CHECK_LEN],
+    };
+    let bytes = header.to_bytes();
+    assert_eq!(bytes[..16], MAGIC[..]);
+    assert_eq!(bytes[16..25], [15, 0, 0, 0, 8, 0, 0, 0, 2]);
+    assert_eq!(Header::from_bytes(&bytes).unwrap(), header);
+
+    let mut bad = bytes;
+    bad[15] = 2;
+    assert_eq!(Header::from_bytes(&bad).unwrap_err().exit_code(), 3);
+
+    // log2 N = 0 is not a valid scrypt cost.
+    let mut bad = bytes;
+    bad[16] = 0;
+    assert_eq!(Header::from_bytes(&bad).unwrap_err().exit_code(), 3);
+
+    // N = 2^28 and r = 4 * (2^27 - 1) would need more than 2^64 bytes.
+    let mut bad = bytes;
+    bad[16] = 28;
+    bad[17..21].copy_from_slice(&(4 * ((1u32 << 27) - 1)).to_be_bytes());
+    bad[21..25].copy_from_slice(&1u32.to_be_bytes());
+    assert_eq!(Header::from_bytes(&bad).unwrap_err().exit_code(), 3);
+}
 
-        for k in 0..plaintext.len() % 64 {
-            encrypted_message[j * 64 + k] = block[k] ^ key_stream[k];
-        }
-    }
+#[test]
+fn test_read_line() {
+    assert_eq!(read_line(&b"hunter2\r\nnext\n"[..]).unwrap(), "hunter2");
+    assert_eq!(read_line(&b"hunter2"[..]).unwrap(), "hunter2");
 
-    encrypted_message
+    let long = vec![b'a'; MAX_PASSPHRASE_LEN + 1];
+    assert!(read_line(&long[..]).is_err());
+    assert!(read_line(&[0xff, b'\n'][..]).is_err());
 }
 
 #[test]
-fn test_chacha20_encrypt() {
-    // o  Key = 00:01:02:03:04:05:06:07:08:09:0a:0b:0c:0d:0e:0f:10:11:12:13:
-    //    14:15:16:17:18:19:1a:1b:1c:1d:1e:1f.
-    // o  Nonce = (00:00:00:00:00:00:00:4a:00:00:00:00)
-    // o  Initial block Count = 1.
-    let key: Vec<u8> = vec![
-        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
-        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
-        0x1e, 0x1f,
-    ];
-    let nonce: Vec<u8> = vec![
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4a, 0x00, 0x00, 0x00, 0x00,
-    ];
-    let initial_block_count = 1;
-
-    // Plaintext Sunscreen:
-    // 000 4c 61 64 69 65 73 20 61 6e 64 20 47 65 6e 74 6c Ladies and Gentl
-    // 016 65 6d 65 6e 20 6f 66 20 74 68 65 20 63 6c 61 73 emen of the clas
-    // 032 73 20 6f 66 20 27 39 39 3a 20 49 66 20 49 20 63 s of ’99: If I c
-    // 048 6f 75 6c 64 20 6f 66 66 65 72 20 79 6f 75 20 6f ould offer you o
-    // 064 6e 6c 79 20 6f 6e 65 20 74 69 70 20 66 6f 72 20 nly one tip for
-    // 080 74 68 65 20 66 75 74 75 72 65 2c 20 73 75 6e 73 the future, suns
-    // 096 63 72 65 65 6e 20 77 6f 75 6c 64 20 62 65 20 69 creen would be i
-    // 112 74 2e                                           t.
-    let plain_test = vec![
-        0x4c, 0x61, 0x64, 0x69, 0x65, 0x73, 0x20, 0x61, 0x6e, 0x64, 0x20, 0x47, 0x65, 0x6e, 0x74,
-        0x6c, 0x65, 0x6d, 0x65, 0x6e, 0x20, 0x6f, 0x66, 0x20, 0x74, 0x68, 0x65, 0x20, 0x63, 0x6c,
-        0x61, 0x73, 0x73, 0x20, 0x6f, 0x66, 0x20, 0x27, 0x39, 0x39, 0x3a, 0x20, 0x49, 0x66, 0x20,
-        0x49, 0x20, 0x63, 0x6f, 0x75, 0x6c, 0x64, 0x20, 0x6f, 0x66, 0x66, 0x65, 0x72, 0x20, 0x79,
-        0x6f, 0x75, 0x20, 0x6f, 0x6e, 0x6c, 0x79, 0x20, 0x6f, 0x6e, 0x65, 0x20, 0x74, 0x69, 0x70,
-        0x20, 0x66, 0x6f, 0x72, 0x20, 0x74, 0x68, 0x65, 0x20, 0x66, 0x75, 0x74, 0x75, 0x72, 0x65,
-        0x2c, 0x20, 0x73, 0x75, 0x6e, 0x73, 0x63, 0x72, 0x65, 0x65, 0x6e, 0x20, 0x77, 0x6f, 0x75,
-        0x6c, 0x64, 0x20, 0x62, 0x65, 0x20, 0x69, 0x74, 0x2e,
-    ];
-
-    // Ciphertext Sunscreen:
-    // 000  6e 2e 35 9a 25 68 f9 80 41 ba 07 28 dd 0d 69 81  n.5.%h..A..(..i.
-    // 016  e9 7e 7a ec 1d 43 60 c2 0a 27 af cc fd 9f ae 0b  .~z..C`..'......
-    // 032  f9 1b 65 c5 52 47 33 ab 8f 59 3d ab cd 62 b3 57  ..e.RG3..Y=..b.W
-    // 048  16 39 d6 24 e6 51 52 ab 8f 53 0c 35 9f 08 61 d8  .9.$.QR..S.5..a.
-    // 064  07 ca 0d bf 50 0d 6a 61 56 a3 8e 08 8a 22 b6 5e  ....P.jaV....".^
-    // 080  52 bc 51 4d 16 cc f8 06 81 8c e9 1a b7 79 37 36  R.QM.........y76
-    // 096  5a f9 0b bf 74 a3 5b e6 b4 0b 8e ed f2 78 5e 42  Z...t.[......x^B
-    // 112  87 4d                                            .M
-    let cipher_text = vec![
-        0x6e, 0x2e, 0x35, 0x9a, 0x25, 0x68, 0xf9, 0x80, 0x41, 0xba, 0x07, 0x28, 0xdd, 0x0d, 0x69,
-        0x81, 0xe9, 0x7e, 0x7a, 0xec, 0x1d, 0x43, 0x60, 0xc2, 0x0a, 0x27, 0xaf, 0xcc, 0xfd, 0x9f,
-        0xae, 0x0b, 0xf9, 0x1b, 0x65, 0xc5, 0x52, 0x47, 0x33, 0xab, 0x8f, 0x59, 0x3d, 0xab, 0xcd,
-        0x62, 0xb3, 0x57, 0x16, 0x39, 0xd6, 0x24, 0xe6, 0x51, 0x52, 0xab, 0x8f, 0x53, 0x0c, 0x35,
-        0x9f, 0x08, 0x61, 0xd8, 0x07, 0xca, 0x0d, 0xbf, 0x50, 0x0d, 0x6a, 0x61, 0x56, 0xa3, 0x8e,
-        0x08, 0x8a, 0x22, 0xb6, 0x5e, 0x52, 0xbc, 0x51, 0x4d, 0x16, 0xcc, 0xf8, 0x06, 0x81, 0x8c,
-        0xe9, 0x1a, 0xb7, 0x79, 0x37, 0x36, 0x5a, 0xf9, 0x0b, 0xbf, 0x74, 0xa3, 0x5b, 0xe6, 0xb4,
-        0x0b, 0x8e, 0xed, 0xf2, 0x78, 0x5e, 0x42, 0x87, 0x4d,
-    ];
-
-    let actual = chacha20_encrypt(key, initial_block_count, nonce, plain_test);
-
-    assert_eq!(cipher_text, actual);
+fn test_passphrase_round_trip() {
+    let params = ScryptParams::new(4, 1, 1).unwrap();
+    let plaintext = b"Ladies and Gentlemen of the class of '99";
+
+    let mut ciphertext = Vec::new();
+    let mut cipher = write_header(b"hunter2", &params, &mut ciphertext).unwrap();
+    stream(&mut cipher, &mut &plaintext[..], &mut ciphertext, "test").unwrap();
+    assert_eq!(ciphertext.len(), HEADER_LEN + plaintext.len());
+
+    let mut input = &ciphertext[..];
+    let mut cipher = read_header(b"hunter2", &mut input, "test").unwrap();
+    let mut decrypted = Vec::new();
+    stream(&mut cipher, &mut input, &mut decrypted, "test").unwrap();
+    assert_eq!(decrypted[..], plaintext[..]);
+
+    // Each encryption has its own salt, so its own key.
+    let mut again = Vec::new();
+    let mut cipher = write_header(b"hunter2", &params, &mut again).unwrap();
+    stream(&mut cipher, &mut &plaintext[..], &mut again, "test").unwrap();
+    assert_ne!(again[HEADER_LEN..], ciphertext[HEADER_LEN..]);
+
+    let err = read_header(b"hunter3", &mut &ciphertext[..], "test")
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "wrong passphrase");
+    let err = read_header(b"hunter2", &mut &plaintext[..], "test")
+        .err()
+        .unwrap();
+    assert_eq!(err.exit_code(), 3);
 }
 
 #[test]
-fn test_generate_rng() {
-    let seed: Vec<u8> = vec![
-        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
-        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
-        0x1e, 0x1f,
-    ];
-
-    let nonce: Vec<u8> = vec![
-        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    ];
-
-    let expected: Vec<u32> = vec![
-        2100034873, 1780073945, 1996733837, 1229642936, 1876440458, 3429555900, 1283312818,
-        2451892952, 3888915243, 2871222434, 1777274431, 1686095930, 3929375269, 765720497,
-        2690787266, 205609800, 826456088, 3517376173, 1633444115, 659440559, 4126388728,
-        1549512161, 318568684, 1551185194, 1829242994, 1564274385, 609780125, 1006636644,
-        1593221275, 3461963230, 2135566861, 3445265713, 3693998658, 3583134375, 4018841452,
-        997363241, 914301792, 3082742343, 815587571, 3806560462,
-    ];
-
-    let r = block_function(seed.clone(), 0, nonce.clone());
-    for i in 0..16 {
-        assert_eq!(expected[i], r[i]);
+fn test_read_header_memory_limit() {
+    let header = Header {
+        params: ScryptParams::new(21, 8, 1).unwrap(),
+        salt: [0; SALT_LEN],
+        check: [0; CHECK_LEN],
+    };
+    let err = read_header(b"hunter2", &mut &header.to_bytes()[..], "test")
+        .err()
+        .unwrap();
+    assert_eq!(err.exit_code(), 3);
+}
+
+#[test]
+fn test_read_header_work_limit() {
+    // A small V, but a huge p: the first needs 64 GiB for B, the second
+    // only 4 MiB but 2^25 block mixes.
+    for &(log_n, p) in [(1, 1 << 29), (10, 1 << 15)].iter() {
+        let header = Header {
+            params: ScryptParams::new(log_n, 1, p).unwrap(),
+            salt: [0; SALT_LEN],
+            check: [0; CHECK_LEN],
+        };
+        let err = read_header(b"hunter2", &mut &header.to_bytes()[..], "test")
+            .err()
+            .unwrap();
+        assert_eq!(err.exit_code(), 3, "{} {}", log_n, p);
     }
+}
 
-    let r = block_function(seed.clone(), 1, nonce.clone());
-    for i in 0..16 {
-        assert_eq!(expected[i + 16], r[i]);
+#[test]
+fn test_stream_sunscreen() {
+    // 2.4.2.  Example and Test Vector for the ChaCha20 Cipher, fed through
+    // a reader that returns a few bytes at a time.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(7);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
     }
+
+    let key =
+        Key::from_hex("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap();
+    let nonce = Nonce::from_hex("000000000000004a00000000").unwrap();
+    let plaintext = b"Ladies and Gentlemen of the class of '99: \
+If I could offer you only one tip for the future, sunscreen would be it.";
+
+    let mut cipher = ChaCha20::new(&key, &nonce);
+    cipher.seek(64).unwrap();
+    let mut ciphertext = Vec::new();
+    stream(
+        &mut cipher,
+        &mut Trickle(plaintext),
+        &mut ciphertext,
+        "test",
+    )
+    .unwrap();
+    assert_eq!(
+        ciphertext,
+        ChaCha20::new(&key, &nonce).encrypt(1, plaintext).unwrap()
+    );
+    assert_eq!(ciphertext[..4], [0x6e, 0x2e, 0x35, 0x9a]);
+
+    let mut cipher = ChaCha20::new(&key, &nonce);
+    cipher.seek(64).unwrap();
+    let mut decrypted = Vec::new();
+    stream(&mut cipher, &mut &ciphertext[..], &mut decrypted, "test").unwrap();
+    assert_eq!(decrypted[..], plaintext[..]);
 }
@@ -0,0 +1,564 @@
+// 2.5.  The Poly1305 Algorithm
+
+//    Poly1305 is a one-time authenticator designed by D. J. Bernstein.
+//    Poly1305 takes a 32-byte one-time key and a message and produces a
+//    16-byte tag.  This tag is used to authenticate the message.
+
+//    The accumulator and "r" are kept as five 26-bit limbs so that every
+//    product fits in a u64 and no big-number arithmetic is needed.
+
+use crate::chacha20::{init_state, keystream_block};
+use crate::cipher::{Key, Nonce};
+use crate::wipe::wipe;
+
+fn load32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Incremental Poly1305 computation.  The key and the accumulator are wiped
+/// when the value is dropped.
+pub struct Poly1305 {
+    r: [u32; 5],
+    h: [u32; 5],
+    pad: [u32; 4],
+    buffer: [u8; 16],
+    leftover: usize,
+}
+
+impl Drop for Poly1305 {
+    fn drop(&mut self) {
+        wipe(&mut self.r);
+        wipe(&mut self.h);
+        wipe(&mut self.pad);
+        wipe(&mut self.buffer);
+    }
+}
+
+impl Poly1305 {
+    pub fn new(key: &[u8; 32]) -> Poly1305 {
+        //    o  First, the "r" value should be clamped.
+        //       r &= 0xffffffc0ffffffc0ffffffc0fffffff
+        let r = [
+            load32(&key[0..]) & 0x3ff_ffff,
+            (load32(&key[3..]) >> 2) & 0x3ff_ff03,
+            (load32(&key[6..]) >> 4) & 0x3ff_c0ff,
+            (load32(&key[9..]) >> 6) & 0x3f0_3fff,
+            (load32(&key[12..]) >> 8) & 0x00f_ffff,
+        ];
+
+        //    o  "s" is the second half of the key, added at the very end.
+        let pad = [
+            load32(&key[16..]),
+            load32(&key[20..]),
+            load32(&key[24..]),
+            load32(&key[28..]),
+        ];
+
+        Poly1305 {
+            r,
+            h: [0; 5],
+            pad,
+            buffer: [0; 16],
+            leftover: 0,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut data = data;
+
+        if self.leftover > 0 {
+            let n = (16 - self.leftover).min(data.len());
+            self.buffer[self.leftover..self.leftover + n].copy_from_slice(&data[..n]);
+            self.leftover += n;
+            data = &data[n..];
+
+            if self.leftover < 16 {
+                return;
+            }
+            let block = self.buffer;
+            self.block(&block, 1 << 24);
+            self.leftover = 0;
+        }
+
+        let mut blocks = data.chunks_exact(16);
+        for block in &mut blocks {
+            self.block(block, 1 << 24);
+        }
+
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.leftover = rest.len();
+    }
+
+    pub fn finalize(mut self) -> [u8; 16] {
+        //    If the last block is shorter than 16 bytes, the one byte is
+        //    added right after the message and the block is zero-padded
+        //    instead of setting bit 128.
+        if self.leftover > 0 {
+            let mut block = [0u8; 16];
+            block[..self.leftover].copy_from_slice(&self.buffer[..self.leftover]);
+            block[self.leftover] = 1;
+            self.block(&block, 0);
+        }
+
+        let [mut h0, mut h1, mut h2, mut h3, mut h4] = self.h;
+
+        // Fully carry h.
+        let mut c = h1 >> 26;
+        h1 &= 0x3ff_ffff;
+        h2 += c;
+        c = h2 >> 26;
+        h2 &= 0x3ff_ffff;
+        h3 += c;
+        c = h3 >> 26;
+        h3 &= 0x3ff_ffff;
+        h4 += c;
+        c = h4 >> 26;
+        h4 &= 0x3ff_ffff;
+        h0 += c * 5;
+        c = h0 >> 26;
+        h0 &= 0x3ff_ffff;
+        h1 += c;
+
+        // Compute g = h + -p = h - (2^130 - 5).
+        let mut g0 = h0.wrapping_add(5);
+        c = g0 >> 26;
+        g0 &= 0x3ff_ffff;
+        let mut g1 = h1.wrapping_add(c);
+        c = g1 >> 26;
+        g1 &= 0x3ff_ffff;
+        let mut g2 = h2.wrapping_add(c);
+        c = g2 >> 26;
+        g2 &= 0x3ff_ffff;
+        let mut g3 = h3.wrapping_add(c);
+        c = g3 >> 26;
+        g3 &= 0x3ff_ffff;
+        let mut g4 = h4.wrapping_add(c).wrapping_sub(1 << 26);
+
+        // Select h if h < p, or g = h - p if h >= p, without branching.
+        let mut mask = (g4 >> 31).wrapping_sub(1);
+        g0 &= mask;
+        g1 &= mask;
+        g2 &= mask;
+        g3 &= mask;
+        g4 &= mask;
+        mask = !mask;
+        h0 = (h0 & mask) | g0;
+        h1 = (h1 & mask) | g1;
+        h2 = (h2 & mask) | g2;
+        h3 = (h3 & mask) | g3;
+        h4 = (h4 & mask) | g4;
+
+        // h = h % 2^128
+        h0 |= h1 << 26;
+        h1 = (h1 >> 6) | (h2 << 20);
+        h2 = (h2 >> 12) | (h3 << 14);
+        h3 = (h3 >> 18) | (h4 << 8);
+
+        //    Finally, the value of the secret key "s" is added to the
+        //    accumulator, and the 128 least significant bits are serialized
+        //    in little-endian order to form the tag.
+        let mut f = u64::from(h0) + u64::from(self.pad[0]);
+        h0 = f as u32;
+        f = u64::from(h1) + u64::from(self.pad[1]) + (f >> 32);
+        h1 = f as u32;
+        f = u64::from(h2) + u64::from(self.pad[2]) + (f >> 32);
+        h2 = f as u32;
+        f = u64::from(h3) + u64::from(self.pad[3]) + (f >> 32);
+        h3 = f as u32;
+
+        let mut tag = [0u8; 16];
+        tag[0..4].copy_from_slice(&h0.to_le_bytes());
+        tag[4..8].copy_from_slice(&h1.to_le_bytes());
+        tag[8..12].copy_from_slice(&h2.to_le_bytes());
+        tag[12..16].copy_from_slice(&h3.to_le_bytes());
+        tag
+    }
+
+    //    Divide the message into 16-byte blocks.  Add one bit beyond the
+    //    number of octets, add the block to the accumulator, multiply by
+    //    "r" and reduce modulo P = 2^130 - 5.
+    fn block(&mut self, m: &[u8], hibit: u32) {
+        let [r0, r1, r2, r3, r4] = self.r;
+        let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+        let [mut h0, mut h1, mut h2, mut h3, mut h4] = self.h;
+
+        h0 += load32(&m[0..]) & 0x3ff_ffff;
+        h1 += (load32(&m[3..]) >> 2) & 0x3ff_ffff;
+        h2 += (load32(&m[6..]) >> 4) & 0x3ff_ffff;
+        h3 += (load32(&m[9..]) >> 6) & 0x3ff_ffff;
+        h4 += (load32(&m[12..]) >> 8) | hibit;
+
+        let mul = |a: u32, b: u32| u64::from(a) * u64::from(b);
+        let d0 = mul(h0, r0) + mul(h1, s4) + mul(h2, s3) + mul(h3, s2) + mul(h4, s1);
+        let mut d1 = mul(h0, r1) + mul(h1, r0) + mul(h2, s4) + mul(h3, s3) + mul(h4, s2);
+        let mut d2 = mul(h0, r2) + mul(h1, r1) + mul(h2, r0) + mul(h3, s4) + mul(h4, s3);
+        let mut d3 = mul(h0, r3) + mul(h1, r2) + mul(h2, r1) + mul(h3, r0) + mul(h4, s4);
+        let mut d4 = mul(h0, r4) + mul(h1, r3) + mul(h2, r2) + mul(h3, r1) + mul(h4, r0);
+
+        let mut c = d0 >> 26;
+        h0 = d0 as u32 & 0x3ff_ffff;
+        d1 += c;
+        c = d1 >> 26;
+        h1 = d1 as u32 & 0x3ff_ffff;
+        d2 += c;
+        c = d2 >> 26;
+        h2 = d2 as u32 & 0x3ff_ffff;
+        d3 += c;
+        c = d3 >> 26;
+        h3 = d3 as u32 & 0x3ff_ffff;
+        d4 += c;
+        c = d4 >> 26;
+        h4 = d4 as u32 & 0x3ff_ffff;
+        h0 += c as u32 * 5;
+        let c = h0 >> 26;
+        h0 &= 0x3ff_ffff;
+        h1 += c;
+
+        self.h = [h0, h1, h2, h3, h4];
+    }
+}
+
+/// Computes the Poly1305 tag of `msg` under the one-time `key`.
+pub fn poly1305(key: &[u8; 32], msg: &[u8]) -> [u8; 16] {
+    let mut mac = Poly1305::new(key);
+    mac.update(msg);
+    mac.finalize()
+}
+
+// 2.6.  Generating the Poly1305 Key Using ChaCha20
+
+//    The method is to call the block function with the following
+//    parameters:
+
+//    o  The 256-bit session integrity key is used as the ChaCha20 key.
+//    o  The block counter is set to zero.
+//    o  The protocol will specify a 96-bit or 64-bit nonce.  This MUST be
+//       unique per invocation with the same key, so it MUST NOT be
+//       randomly generated.
+
+//    After running the block function, we have a 512-bit state.  We take
+//    the first 256 bits of the serialized state, and use those as the one-
+//    time Poly1305 key.
+
+/// Derives the one-time Poly1305 key for `nonce` from a ChaCha20 key.
+pub fn poly1305_key_gen(key: &Key, nonce: &Nonce) -> [u8; 32] {
+    let mut state = init_state(key.as_bytes(), 0, nonce.as_bytes());
+    let mut block = [0u8; 64];
+    keystream_block::<20>(&state, &mut block);
+
+    let mut otk = [0u8; 32];
+    otk.copy_from_slice(&block[..32]);
+    wipe(&mut state);
+    wipe(&mut block);
+    otk
+}
+
+#[cfg(test)]
+fn key_from_r_s(r: [u8; 16], s: [u8; 16]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[..16].copy_from_slice(&r);
+    key[16..].copy_from_slice(&s);
+    key
+}
+
+#[cfg(test)]
+const IETF_CONTRIBUTION: &[u8] = b"Any submission to the IETF intended by the Contributor for \
+publication as all or part of an IETF Internet-Draft or RFC and any statement made within the \
+context of an IETF activity is considered an \"IETF Contribution\". Such statements include oral \
+statements in IETF sessions, as well as written and electronic communications made at any time \
+or place, which are addressed to";
+
+// 2.5.2.  Poly1305 Example and Test Vector
+
+//    Key Material: 85:d6:be:78:57:55:6d:33:7f:44:52:fe:42:d5:06:a8:01:
+//    03:80:8a:fb:0d:b2:fd:4a:bf:f6:af:41:49:f5:1b
+//    Message to be Authenticated: "Cryptographic Forum Research Group"
+//    Tag: a8:06:1d:c1:30:51:36:c6:c2:2b:8b:af:0c:01:27:a9
+
+#[test]
+fn test_poly1305() {
+    let key = [
+        0x85, 0xd6, 0xbe, 0x78, 0x57, 0x55, 0x6d, 0x33, 0x7f, 0x44, 0x52, 0xfe, 0x42, 0xd5, 0x06,
+        0xa8, 0x01, 0x03, 0x80, 0x8a, 0xfb, 0x0d, 0xb2, 0xfd, 0x4a, 0xbf, 0xf6, 0xaf, 0x41, 0x49,
+        0xf5, 0x1b,
+    ];
+    let expected = [
+        0xa8, 0x06, 0x1d, 0xc1, 0x30, 0x51, 0x36, 0xc6, 0xc2, 0x2b, 0x8b, 0xaf, 0x0c, 0x01, 0x27,
+        0xa9,
+    ];
+
+    assert_eq!(
+        poly1305(&key, b"Cryptographic Forum Research Group"),
+        expected
+    );
+}
+
+#[test]
+fn test_poly1305_incremental() {
+    let key = [
+        0x85, 0xd6, 0xbe, 0x78, 0x57, 0x55, 0x6d, 0x33, 0x7f, 0x44, 0x52, 0xfe, 0x42, 0xd5, 0x06,
+        0xa8, 0x01, 0x03, 0x80, 0x8a, 0xfb, 0x0d, 0xb2, 0xfd, 0x4a, 0xbf, 0xf6, 0xaf, 0x41, 0x49,
+        0xf5, 0x1b,
+    ];
+    let expected = poly1305(&key, IETF_CONTRIBUTION);
+
+    for chunk in 1..=40 {
+        let mut mac = Poly1305::new(&key);
+        for piece in IETF_CONTRIBUTION.chunks(chunk) {
+            mac.update(piece);
+        }
+        assert_eq!(mac.finalize(), expected, "chunk size {}", chunk);
+    }
+}
+
+// A.3.  Poly1305 Message Authentication Code
+
+#[test]
+fn test_poly1305_vector_1() {
+    assert_eq!(poly1305(&[0; 32], &[0; 64]), [0; 16]);
+}
+
+#[test]
+fn test_poly1305_vector_2() {
+    let s = [
+        0x36, 0xe5, 0xf6, 0xb5, 0xc5, 0xe0, 0x60, 0x70, 0xf0, 0xef, 0xca, 0x96, 0x22, 0x7a, 0x86,
+        0x3e,
+    ];
+    let key = key_from_r_s([0; 16], s);
+
+    assert_eq!(poly1305(&key, IETF_CONTRIBUTION), s);
+}
+
+#[test]
+fn test_poly1305_vector_3() {
+    let r = [
+        0x36, 0xe5, 0xf6, 0xb5, 0xc5, 0xe0, 0x60, 0x70, 0xf0, 0xef, 0xca, 0x96, 0x22, 0x7a, 0x86,
+        0x3e,
+    ];
+    let key = key_from_r_s(r, [0; 16]);
+    let expected = [
+        0xf3, 0x47, 0x7e, 0x7c, 0xd9, 0x54, 0x17, 0xaf, 0x89, 0xa6, 0xb8, 0x79, 0x4c, 0x31, 0x0c,
+        0xf0,
+    ];
+
+    assert_eq!(poly1305(&key, IETF_CONTRIBUTION), expected);
+}
+
+#[test]
+fn test_poly1305_vector_4() {
+    let key = [
+        0x1c, 0x92, 0x40, 0xa5, 0xeb, 0x55, 0xd3, 0x8a, 0xf3, 0x33, 0x88, 0x86, 0x04, 0xf6, 0xb5,
+        0xf0, 0x47, 0x39, 0x17, 0xc1, 0x40, 0x2b, 0x80, 0x09, 0x9d, 0xca, 0x5c, 0xbc, 0x20, 0x70,
+        0x75, 0xc0,
+    ];
+    let msg = b"'Twas brillig, and the slithy toves\nDid gyre and gimble in the wabe:\n\
+All mimsy were the borogoves,\nAnd the mome raths outgrabe.";
+    let expected = [
+        0x45, 0x41, 0x66, 0x9a, 0x7e, 0xaa, 0xee, 0x61, 0xe7, 0x08, 0xdc, 0x7c, 0xbc, 0xc5, 0xeb,
+        0x62,
+    ];
+
+    assert_eq!(poly1305(&key, msg), expected);
+}
+
+#[test]
+fn test_poly1305_vector_5() {
+    // If one uses 130-bit partial reduction, does the code handle the case
+    // where partially reduced final result is not fully reduced?
+    let mut r = [0; 16];
+    r[0] = 0x02;
+    let key = key_from_r_s(r, [0; 16]);
+    let mut expected = [0; 16];
+    expected[0] = 0x03;
+
+    assert_eq!(poly1305(&key, &[0xff; 16]), expected);
+}
+
+#[test]
+fn test_poly1305_vector_6() {
+    // What happens if addition of s overflows modulo 2^128?
+    let mut r = [0; 16];
+    r[0] = 0x02;
+    let key = key_from_r_s(r, [0xff; 16]);
+    let mut msg = [0; 16];
+    msg[0] = 0x02;
+    let mut expected = [0; 16];
+    expected[0] = 0x03;
+
+    assert_eq!(poly1305(&key, &msg), expected);
+}
+
+#[test]
+fn test_poly1305_vector_7() {
+    // What happens if data limb is all ones and there is carry from lower
+    // limb?
+    let mut r = [0; 16];
+    r[0] = 0x01;
+    let key = key_from_r_s(r, [0; 16]);
+    let mut msg = [0xff; 48];
+    msg[16] = 0xf0;
+    msg[32..].copy_from_slice(&[0; 16]);
+    msg[32] = 0x11;
+    let mut expected = [0; 16];
+    expected[0] = 0x05;
+
+    assert_eq!(poly1305(&key, &msg), expected);
+}
+
+#[test]
+fn test_poly1305_vector_8() {
+    // What happens if final result from polynomial part is exactly 2^130-5?
+    let mut r = [0; 16];
+    r[0] = 0x01;
+    let key = key_from_r_s(r, [0; 16]);
+    let mut msg = [0xff; 48];
+    msg[16..32].copy_from_slice(&[0xfe; 16]);
+    msg[16] = 0xfb;
+    msg[32..].copy_from_slice(&[0x01; 16]);
+
+    assert_eq!(poly1305(&key, &msg), [0; 16]);
+}
+
+#[test]
+fn test_poly1305_vector_9() {
+    // What happens if final result from polynomial part is exactly 2^130-6?
+    let mut r = [0; 16];
+    r[0] = 0x02;
+    let key = key_from_r_s(r, [0; 16]);
+    let mut msg = [0xff; 16];
+    msg[0] = 0xfd;
+    let mut expected = [0xff; 16];
+    expected[0] = 0xfa;
+
+    assert_eq!(poly1305(&key, &msg), expected);
+}
+
+#[test]
+fn test_poly1305_vector_10() {
+    // What happens if 5*H+L-type reduction produces 131-bit intermediate
+    // result?
+    let mut r = [0; 16];
+    r[0] = 0x01;
+    r[8] = 0x04;
+    let key = key_from_r_s(r, [0; 16]);
+    let msg = [
+        0xe3, 0x35, 0x94, 0xd7, 0x50, 0x5e, 0x43, 0xb9, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x33, 0x94, 0xd7, 0x50, 0x5e, 0x43, 0x79, 0xcd, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+    ];
+    let mut expected = [0; 16];
+    expected[0] = 0x14;
+    expected[8] = 0x55;
+
+    assert_eq!(poly1305(&key, &msg), expected);
+}
+
+#[test]
+fn test_poly1305_vector_11() {
+    // What happens if 5*H+L-type reduction produces 131-bit final result?
+    let mut r = [0; 16];
+    r[0] = 0x01;
+    r[8] = 0x04;
+    let key = key_from_r_s(r, [0; 16]);
+    let msg = [
+        0xe3, 0x35, 0x94, 0xd7, 0x50, 0x5e, 0x43, 0xb9, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x33, 0x94, 0xd7, 0x50, 0x5e, 0x43, 0x79, 0xcd, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00,
+    ];
+    let mut expected = [0; 16];
+    expected[0] = 0x13;
+
+    assert_eq!(poly1305(&key, &msg), expected);
+}
+
+// 2.6.2.  Poly1305 Key Generation Test Vector
+
+#[test]
+fn test_poly1305_key_gen() {
+    // Key:
+    // 000  80 81 82 83 84 85 86 87 88 89 8a 8b 8c 8d 8e 8f  ................
+    // 016  90 91 92 93 94 95 96 97 98 99 9a 9b 9c 9d 9e 9f  ................
+    let mut key = [0u8; 32];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = 0x80 + i as u8;
+    }
+
+    // Nonce:
+    // 000  00 00 00 00 00 01 02 03 04 05 06 07              ............
+    let nonce = [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+    ];
+
+    // Poly1305 Key:
+    // 000  8a d5 a0 8b 90 5f 81 cc 81 50 40 27 4a b2 94 71  ....._...P@'J..q
+    // 016  a8 33 b6 37 e3 fd 0d a5 08 db b8 e2 fd d1 a6 46  .3.7...........F
+    let expected = [
+        0x8a, 0xd5, 0xa0, 0x8b, 0x90, 0x5f, 0x81, 0xcc, 0x81, 0x50, 0x40, 0x27, 0x4a, 0xb2, 0x94,
+        0x71, 0xa8, 0x33, 0xb6, 0x37, 0xe3, 0xfd, 0x0d, 0xa5, 0x08, 0xdb, 0xb8, 0xe2, 0xfd, 0xd1,
+        0xa6, 0x46,
+    ];
+
+    assert_eq!(
+        poly1305_key_gen(&Key::new(key), &Nonce::new(nonce)),
+        expected
+    );
+}
+
+// A.4.  Poly1305 Key Generation Using ChaCha20
+
+#[test]
+fn test_poly1305_key_gen_vector_1() {
+    let expected = [
+        0x76, 0xb8, 0xe0, 0xad, 0xa0, 0xf1, 0x3d, 0x90, 0x40, 0x5d, 0x6a, 0xe5, 0x53, 0x86, 0xbd,
+        0x28, 0xbd, 0xd2, 0x19, 0xb8, 0xa0, 0x8d, 0xed, 0x1a, 0xa8, 0x36, 0xef, 0xcc, 0x8b, 0x77,
+        0x0d, 0xc7,
+    ];
+
+    assert_eq!(
+        poly1305_key_gen(&Key::new([0; 32]), &Nonce::new([0; 12])),
+        expected
+    );
+}
+
+#[test]
+fn test_poly1305_key_gen_vector_2() {
+    let mut key = [0u8; 32];
+    key[31] = 0x01;
+    let mut nonce = [0u8; 12];
+    nonce[11] = 0x02;
+    let expected = [
+        0xec, 0xfa, 0x25, 0x4f, 0x84, 0x5f, 0x64, 0x74, 0x73, 0xd3, 0xcb, 0x14, 0x0d, 0xa9, 0xe8,
+        0x76, 0x06, 0xcb, 0x33, 0x06, 0x6c, 0x44, 0x7b, 0x87, 0xbc, 0x26, 0x66, 0xdd, 0xe3, 0xfb,
+        0xb7, 0x39,
+    ];
+
+    assert_eq!(
+        poly1305_key_gen(&Key::new(key), &Nonce::new(nonce)),
+        expected
+    );
+}
+
+#[test]
+fn test_poly1305_key_gen_vector_3() {
+    let key = [
+        0x1c, 0x92, 0x40, 0xa5, 0xeb, 0x55, 0xd3, 0x8a, 0xf3, 0x33, 0x88, 0x86, 0x04, 0xf6, 0xb5,
+        0xf0, 0x47, 0x39, 0x17, 0xc1, 0x40, 0x2b, 0x80, 0x09, 0x9d, 0xca, 0x5c, 0xbc, 0x20, 0x70,
+        0x75, 0xc0,
+    ];
+    let mut nonce = [0u8; 12];
+    nonce[11] = 0x02;
+    let expected = [
+        0x96, 0x5e, 0x3b, 0xc6, 0xf9, 0xec, 0x7e, 0xd9, 0x56, 0x08, 0x08, 0xf4, 0xd2, 0x29, 0xf9,
+        0x4b, 0x13, 0x7f, 0xf2, 0x75, 0xca, 0x9b, 0x3f, 0xcb, 0xdd, 0x59, 0xde, 0xaa, 0xd2, 0x33,
+        0x10, 0xae,
+    ];
+
+    assert_eq!(
+        poly1305_key_gen(&Key::new(key), &Nonce::new(nonce)),
+        expected
+    );
+}
@@ -0,0 +1,319 @@
+use crate::backend::xor_keystream;
+use crate::chacha20::init_state;
+use crate::wipe::wipe;
+use rand_core::{CryptoRng, Error, OsRng, RngCore};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(test)]
+use std::vec;
+
+// Each refill makes 16 blocks of key stream; the first 32 bytes become the
+// next key and the rest is output.
+const BUFFER_LEN: usize = 16 * 64;
+
+const DEFAULT_RESEED_INTERVAL: u64 = 1 << 20;
+
+static RESEED_INTERVAL: AtomicU64 = AtomicU64::new(DEFAULT_RESEED_INTERVAL);
+
+// Incremented in the child after every fork(), so a generator copied into a
+// new process sees a different value than the one it was seeded under.
+// Unlike the process id this costs no system call to read, and a child
+// whose id was reused from an earlier process is still caught.
+static FORK_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+std::thread_local! {
+    static GENERATOR: RefCell<Option<FastKeyErasure>> = const { RefCell::new(None) };
+}
+
+/// Fills `dest` with cryptographically secure random bytes.
+///
+/// Each thread has its own ChaCha20 generator, seeded from the operating
+/// system on first use, so threads never wait on each other.  The generator
+/// follows the fast-key-erasure design: every refill overwrites the key with
+/// fresh key stream, and output bytes are wiped from the buffer as they are
+/// handed out, so a later compromise of memory does not reveal earlier
+/// output.  The generator also reseeds from the operating system after
+/// `set_reseed_interval` bytes, and after `fork()`, so a child never repeats
+/// its parent's stream.  Forks are seen through `pthread_atfork`, so a child
+/// made by a raw `clone` system call, which skips the fork handlers, is not
+/// detected.
+///
+/// Panics if the operating system cannot provide a seed.
+pub fn random_bytes(dest: &mut [u8]) {
+    let interval = RESEED_INTERVAL.load(Ordering::Relaxed);
+
+    GENERATOR.with(|generator| {
+        generator
+            .borrow_mut()
+            .get_or_insert_with(FastKeyErasure::new)
+            .fill(dest, interval)
+    })
+}
+
+/// Sets how many bytes each thread's generator may produce before it reseeds
+/// from the operating system.  The default is 1 MiB.  Takes effect at each
+/// generator's next refill.
+pub fn set_reseed_interval(bytes: u64) {
+    RESEED_INTERVAL.store(bytes, Ordering::Relaxed);
+}
+
+/// A handle to the `random_bytes` generator for code that takes a
+/// `rand_core` RNG.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemRng;
+
+impl RngCore for SystemRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        random_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        random_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        random_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        random_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for SystemRng {}
+
+#[cfg(unix)]
+extern "C" fn after_fork() {
+    FORK_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+// The current fork generation.  The first call registers the handler that
+// advances it; forks before then cannot copy a generator, as none exists.
+// Systems without pthread_atfork have no fork() to detect.
+fn fork_generation() -> u64 {
+    #[cfg(unix)]
+    {
+        static REGISTER: std::sync::Once = std::sync::Once::new();
+        REGISTER.call_once(|| {
+            // after_fork only touches an atomic, which is safe in a child
+            // that may have been forked from a multithreaded process.
+            let ret = unsafe { libc::pthread_atfork(None, None, Some(after_fork)) };
+            assert_eq!(ret, 0, "cannot register fork handler");
+        });
+    }
+    FORK_GENERATION.load(Ordering::Relaxed)
+}
+
+// Bernstein's fast-key-erasure RNG (https://blog.cr.yp.to/20170723-random.html)
+// on ChaCha20 with a zero nonce.
+struct FastKeyErasure {
+    key: [u8; 32],
+    buffer: [u8; BUFFER_LEN],
+    // The unused output is the last `available` bytes of `buffer`.
+    available: usize,
+    // Bytes of key stream generated since the last seed.
+    generated: u64,
+    // The fork generation the generator was seeded in.
+    generation: u64,
+}
+
+impl Drop for FastKeyErasure {
+    fn drop(&mut self) {
+        wipe(&mut self.key);
+        wipe(&mut self.buffer);
+    }
+}
+
+impl FastKeyErasure {
+    fn new() -> FastKeyErasure {
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        let generator = FastKeyErasure::from_key(key);
+        wipe(&mut key);
+        generator
+    }
+
+    fn from_key(key: [u8; 32]) -> FastKeyErasure {
+        FastKeyErasure {
+            key,
+            buffer: [0; BUFFER_LEN],
+            available: 0,
+            generated: 0,
+            generation: fork_generation(),
+        }
+    }
+
+    // Replaces the key with one from the operating system and drops any
+    // buffered output.
+    fn reseed(&mut self) {
+        OsRng.fill_bytes(&mut self.key);
+        self.buffer = [0; BUFFER_LEN];
+        self.available = 0;
+        self.generated = 0;
+        self.generation = fork_generation();
+    }
+
+    fn refill(&mut self) {
+        self.buffer = [0; BUFFER_LEN];
+        let mut state = init_state(&self.key, 0, &[0; 12]);
+        xor_keystream::<20>(&state, &mut self.buffer);
+        wipe(&mut state);
+
+        self.key.copy_from_slice(&self.buffer[..32]);
+        wipe(&mut self.buffer[..32]);
+        self.available = BUFFER_LEN - 32;
+        self.generated += BUFFER_LEN as u64;
+    }
+
+    fn fill(&mut self, dest: &mut [u8], reseed_interval: u64) {
+        if self.generation != fork_generation() {
+            self.reseed();
+        }
+
+        let mut dest = dest;
+        while !dest.is_empty() {
+            if self.available == 0 {
+                if self.generated >= reseed_interval {
+                    self.reseed();
+                }
+                self.refill();
+            }
+
+            let start = BUFFER_LEN - self.available;
+            let n = self.available.min(dest.len());
+            let (head, rest) = dest.split_at_mut(n);
+            head.copy_from_slice(&self.buffer[start..start + n]);
+            wipe(&mut self.buffer[start..start + n]);
+
+            self.available -= n;
+            dest = rest;
+        }
+    }
+}
+
+#[test]
+fn test_random_bytes() {
+    let mut a = [0u8; 100];
+    let mut b = [0u8; 100];
+    random_bytes(&mut a);
+    random_bytes(&mut b);
+
+    assert_ne!(a, [0; 100]);
+    assert_ne!(a, b);
+}
+
+#[test]
+fn test_random_bytes_per_thread() {
+    let other = std::thread::spawn(|| {
+        let mut bytes = [0u8; 32];
+        random_bytes(&mut bytes);
+        bytes
+    })
+    .join()
+    .unwrap();
+
+    let mut bytes = [0u8; 32];
+    random_bytes(&mut bytes);
+    assert_ne!(bytes, other);
+}
+
+#[test]
+fn test_fast_key_erasure_output() {
+    let key = [7u8; 32];
+    let mut stream = [0u8; BUFFER_LEN];
+    crate::chacha20::chacha20_encrypt_in_place(&key, 0, &[0; 12], &mut stream).unwrap();
+
+    let mut rng = FastKeyErasure::from_key(key);
+    let mut out = [0u8; 100];
+    rng.fill(&mut out[..1], u64::MAX);
+    rng.fill(&mut out[1..], u64::MAX);
+
+    // The first 32 bytes of key stream replace the key and are never output.
+    assert_eq!(out[..], stream[32..132]);
+    assert_eq!(rng.key[..], stream[..32]);
+
+    // Bytes that were handed out are wiped from the buffer.
+    assert!(rng.buffer[..132].iter().all(|&b| b == 0));
+    assert_eq!(rng.buffer[132..], stream[132..]);
+}
+
+#[test]
+fn test_fast_key_erasure_rekeys() {
+    let mut rng = FastKeyErasure::from_key([7u8; 32]);
+    let mut first = vec![0u8; BUFFER_LEN - 32];
+    rng.fill(&mut first, u64::MAX);
+    let second_key = rng.key;
+
+    let mut next = FastKeyErasure::from_key(second_key);
+    let mut expected = vec![0u8; 64];
+    next.fill(&mut expected, u64::MAX);
+
+    let mut second = vec![0u8; 64];
+    rng.fill(&mut second, u64::MAX);
+    assert_eq!(second, expected);
+    assert_ne!(rng.key, second_key);
+}
+
+#[test]
+fn test_fast_key_erasure_reseeds() {
+    let key = [7u8; 32];
+    let mut expected = FastKeyErasure::from_key(key);
+    let mut unseeded = vec![0u8; 2 * BUFFER_LEN];
+    expected.fill(&mut unseeded, u64::MAX);
+
+    // With an interval of one refill, the second refill uses a key from the
+    // operating system instead of the erased one.
+    let mut rng = FastKeyErasure::from_key(key);
+    let mut out = vec![0u8; 2 * BUFFER_LEN];
+    rng.fill(&mut out, BUFFER_LEN as u64);
+    assert_eq!(out[..BUFFER_LEN - 32], unseeded[..BUFFER_LEN - 32]);
+    assert_ne!(out[BUFFER_LEN - 32..], unseeded[BUFFER_LEN - 32..]);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_random_bytes_fork() {
+    // Leave buffered output behind for the child to inherit.
+    random_bytes(&mut [0u8; 32]);
+
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0);
+    if pid == 0 {
+        // The child sends its next bytes to the parent and exits without
+        // returning into its copy of the test harness, even if it panics.
+        let sent = std::panic::catch_unwind(|| {
+            let mut bytes = [0u8; 32];
+            random_bytes(&mut bytes);
+            unsafe { libc::write(fds[1], bytes.as_ptr().cast(), bytes.len()) }
+        });
+        let code = if sent.is_ok_and(|len| len == 32) {
+            0
+        } else {
+            1
+        };
+        unsafe { libc::_exit(code) };
+    }
+
+    // With the write end closed here, the read below sees end of file
+    // rather than waiting forever if the child dies without writing.
+    unsafe { libc::close(fds[1]) };
+    let mut parent = [0u8; 32];
+    random_bytes(&mut parent);
+    let mut child = [0u8; 32];
+    let len = unsafe { libc::read(fds[0], child.as_mut_ptr().cast(), child.len()) };
+    let mut status = 0;
+    unsafe {
+        libc::close(fds[0]);
+        assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+    }
+    assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+    assert_eq!(len, 32);
+    assert_ne!(child, parent);
+}
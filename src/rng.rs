@@ -0,0 +1,419 @@
+use crate::chacha20::{chacha_block, init_state_legacy};
+#[cfg(all(test, feature = "alloc"))]
+use crate::encoding::decode_hex;
+use crate::wipe::wipe;
+#[cfg(test)]
+use alloc::{vec, vec::Vec};
+use rand_core::{impls, CryptoRng, Error, RngCore, SeedableRng};
+
+// The word position counts 32-bit words: 16 per block, with a 64-bit block
+// counter.
+const WORD_POS_MASK: u128 = (1 << 68) - 1;
+
+/// A cryptographically secure random number generator built on ChaCha with
+/// `ROUNDS` rounds.  Use the `ChaCha20Rng`, `ChaCha12Rng` and `ChaCha8Rng`
+/// aliases.
+///
+/// The seed is the key, and the generator uses the original ChaCha layout:
+/// a 64-bit block counter and a 64-bit stream number in place of the nonce.
+/// Output is the serialized key stream read as little-endian words, one
+/// whole block at a time, so it matches `block_function` with a zero nonce
+/// and the `rand_chacha` crate.
+///
+/// The output is a cycle of 2^68 words; the block counter wraps around at
+/// the end instead of failing.  The seed and buffered output are wiped when
+/// the generator is dropped.
+#[derive(Clone)]
+pub struct ChaChaRng<const ROUNDS: usize> {
+    key: [u8; 32],
+    stream: u64,
+    // The block the next refill generates.
+    counter: u64,
+    buffer: [u32; 16],
+    // Next unread word of `buffer`; 16 when the buffer is used up.
+    index: usize,
+}
+
+/// The 20-round generator.
+pub type ChaCha20Rng = ChaChaRng<20>;
+
+/// The 12-round generator, a good default where speed matters.
+pub type ChaCha12Rng = ChaChaRng<12>;
+
+/// The 8-round generator, for simulations and other non-adversarial uses.
+pub type ChaCha8Rng = ChaChaRng<8>;
+
+impl<const ROUNDS: usize> Drop for ChaChaRng<ROUNDS> {
+    fn drop(&mut self) {
+        wipe(&mut self.key);
+        wipe(&mut self.buffer);
+    }
+}
+
+impl<const ROUNDS: usize> ChaChaRng<ROUNDS> {
+    /// Returns the position of the next word of output.
+    pub fn get_word_pos(&self) -> u128 {
+        (u128::from(self.counter) * 16 + self.index as u128).wrapping_sub(16) & WORD_POS_MASK
+    }
+
+    /// Moves to word `word_pos` of the current stream.  Only the low 68 bits
+    /// are used.
+    pub fn set_word_pos(&mut self, word_pos: u128) {
+        let word_pos = word_pos & WORD_POS_MASK;
+        self.counter = (word_pos / 16) as u64;
+        self.index = 16;
+        if !word_pos.is_multiple_of(16) {
+            self.refill();
+            self.index = (word_pos % 16) as usize;
+        }
+    }
+
+    /// Selects one of 2^64 independent streams for the same seed, keeping
+    /// the word position.
+    pub fn set_stream(&mut self, stream: u64) {
+        self.stream = stream;
+        if self.index < 16 {
+            let word_pos = self.get_word_pos();
+            self.set_word_pos(word_pos);
+        }
+    }
+
+    /// Returns the current stream number.
+    pub fn get_stream(&self) -> u64 {
+        self.stream
+    }
+
+    /// Returns a generator with the same seed on stream `stream_id`, at word
+    /// position 0.
+    ///
+    /// Each stream id selects a separate 2^68-word sequence through the
+    /// nonce words of the state, so one seed can feed many parallel workers
+    /// reproducibly: worker `i` uses `split(i)` and gets the same numbers on
+    /// every run and platform.
+    pub fn split(&self, stream_id: u64) -> ChaChaRng<ROUNDS> {
+        let mut rng = ChaChaRng::from_seed(self.key);
+        rng.stream = stream_id;
+        rng
+    }
+
+    /// Skips `words` words of output on the current stream, wrapping around
+    /// at its end.  At most one block is computed, however far the jump.
+    pub fn jump(&mut self, words: u128) {
+        let word_pos = self.get_word_pos().wrapping_add(words);
+        self.set_word_pos(word_pos);
+    }
+
+    /// Returns the seed the generator was created with.
+    pub fn get_seed(&self) -> [u8; 32] {
+        self.key
+    }
+
+    fn refill(&mut self) {
+        let mut state = init_state_legacy(&self.key, self.counter, &[0; 8]);
+        state[14] = self.stream as u32;
+        state[15] = (self.stream >> 32) as u32;
+
+        self.buffer = chacha_block::<ROUNDS>(&state);
+        wipe(&mut state);
+        self.counter = self.counter.wrapping_add(1);
+        self.index = 0;
+    }
+}
+
+impl<const ROUNDS: usize> SeedableRng for ChaChaRng<ROUNDS> {
+    type Seed = [u8; 32];
+
+    fn from_seed(seed: [u8; 32]) -> ChaChaRng<ROUNDS> {
+        ChaChaRng {
+            key: seed,
+            stream: 0,
+            counter: 0,
+            buffer: [0; 16],
+            index: 16,
+        }
+    }
+}
+
+impl<const ROUNDS: usize> RngCore for ChaChaRng<ROUNDS> {
+    fn next_u32(&mut self) -> u32 {
+        if self.index >= 16 {
+            self.refill();
+        }
+
+        let word = self.buffer[self.index];
+        self.index += 1;
+        word
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_u32(self)
+    }
+
+    // Bytes are taken from whole words; the unused bytes of a word that is
+    // only partly needed are discarded.
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let mut filled = 0;
+        while filled < dest.len() {
+            if self.index >= 16 {
+                self.refill();
+            }
+
+            let (words, bytes) =
+                impls::fill_via_u32_chunks(&self.buffer[self.index..], &mut dest[filled..]);
+            self.index += words;
+            filled += bytes;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl<const ROUNDS: usize> CryptoRng for ChaChaRng<ROUNDS> {}
+
+#[cfg(test)]
+fn next_block<R: RngCore>(rng: &mut R) -> [u32; 16] {
+    let mut words = [0u32; 16];
+    for w in words.iter_mut() {
+        *w = rng.next_u32();
+    }
+    words
+}
+
+#[test]
+fn test_rng_matches_generate_rng() {
+    // The same seed and words as `test_generate_rng` in chacha20.rs.
+    let mut seed = [0u8; 32];
+    for (i, b) in seed.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+
+    let expected: Vec<u32> = vec![
+        2100034873, 1780073945, 1996733837, 1229642936, 1876440458, 3429555900, 1283312818,
+        2451892952, 3888915243, 2871222434, 1777274431, 1686095930, 3929375269, 765720497,
+        2690787266, 205609800, 826456088, 3517376173, 1633444115, 659440559, 4126388728,
+        1549512161, 318568684, 1551185194, 1829242994, 1564274385, 609780125, 1006636644,
+        1593221275, 3461963230, 2135566861, 3445265713, 3693998658, 3583134375, 4018841452,
+        997363241, 914301792, 3082742343, 815587571, 3806560462,
+    ];
+
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    for (i, &word) in expected.iter().enumerate() {
+        assert_eq!(rng.next_u32(), word, "word {}", i);
+    }
+}
+
+#[test]
+fn test_rng_true_values() {
+    // draft-nir-cfrg-chacha20-poly1305-04, test vectors 1, 2 and 5.
+    let mut rng = ChaCha20Rng::from_seed([0; 32]);
+    assert_eq!(
+        next_block(&mut rng),
+        [
+            0xade0b876, 0x903df1a0, 0xe56a5d40, 0x28bd8653, 0xb819d2bd, 0x1aed8da0, 0xccef36a8,
+            0xc70d778b, 0x7c5941da, 0x8d485751, 0x3fe02477, 0x374ad8b8, 0xf4b8436a, 0x1ca11815,
+            0x69b687c3, 0x8665eeb2,
+        ]
+    );
+    assert_eq!(
+        next_block(&mut rng),
+        [
+            0xbee7079f, 0x7a385155, 0x7c97ba98, 0x0d082d73, 0xa0290fcb, 0x6965e348, 0x3e53c612,
+            0xed7aee32, 0x7621b729, 0x434ee69c, 0xb03371d5, 0xd539d874, 0x281fed31, 0x45fb0a51,
+            0x1f0ae1ac, 0x6f4d794b,
+        ]
+    );
+
+    // A 96-bit nonce of 0,0,0,0, 0,0,0,0, 0,0,0,2 is stream 2 << 56.
+    let mut rng = ChaCha20Rng::from_seed([0; 32]);
+    rng.set_stream(2 << 56);
+    assert_eq!(
+        next_block(&mut rng),
+        [
+            0x374dc6c2, 0x3736d58c, 0xb904e24a, 0xcd3f93ef, 0x88228b1a, 0x96a4dfb3, 0x5b76ab72,
+            0xc727ee54, 0x0e0e978a, 0xf3145c95, 0x1b748ea8, 0xf786c297, 0x99c28f5f, 0x628314e8,
+            0x398a19fa, 0x6ded1b53,
+        ]
+    );
+}
+
+#[test]
+fn test_rng_true_bytes() {
+    let mut rng = ChaCha20Rng::from_seed([0; 32]);
+    let mut bytes = [0u8; 32];
+    rng.fill_bytes(&mut bytes);
+
+    assert_eq!(
+        bytes,
+        [
+            118, 184, 224, 173, 160, 241, 61, 144, 64, 93, 106, 229, 83, 134, 189, 40, 189, 210,
+            25, 184, 160, 141, 237, 26, 168, 54, 239, 204, 139, 119, 13, 199,
+        ]
+    );
+}
+
+#[test]
+fn test_rng_word_pos() {
+    // Test vector 4 is block 2 of this seed.
+    let mut seed = [0u8; 32];
+    seed[1] = 0xff;
+    let expected = [
+        0xfb4dd572, 0x4bc42ef1, 0xdf922636, 0x327f1394, 0xa78dea8f, 0x5e269039, 0xa1bebbc1,
+        0xcaf09aae, 0xa25ab213, 0x48a6b46c, 0x1b9d9bcb, 0x092c5be6, 0x546ca624, 0x1bec45d5,
+        0x87f47473, 0x96f0992e,
+    ];
+
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    assert_eq!(rng.get_word_pos(), 0);
+    for _ in 0..32 {
+        rng.next_u32();
+    }
+    assert_eq!(next_block(&mut rng), expected);
+    assert_eq!(rng.get_word_pos(), 48);
+
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    rng.set_word_pos(32);
+    assert_eq!(next_block(&mut rng), expected);
+
+    rng.set_word_pos(37);
+    assert_eq!(rng.get_word_pos(), 37);
+    assert_eq!(rng.next_u32(), expected[5]);
+
+    // Partly used words are skipped by `fill_bytes`.
+    let mut buf = [0u8; 32];
+    rng.set_word_pos(48);
+    rng.fill_bytes(&mut buf);
+    assert_eq!(rng.get_word_pos(), 56);
+    rng.fill_bytes(&mut buf[..25]);
+    assert_eq!(rng.get_word_pos(), 63);
+    rng.next_u64();
+    assert_eq!(rng.get_word_pos(), 65);
+    rng.fill_bytes(&mut buf[..1]);
+    assert_eq!(rng.get_word_pos(), 66);
+}
+
+#[test]
+fn test_rng_word_pos_wraps() {
+    let mut rng = ChaCha20Rng::from_seed([0; 32]);
+    let first = next_block(&mut rng);
+
+    let last_block = (1 << 68) - 16;
+    rng.set_word_pos(last_block + 3);
+    assert_eq!(rng.get_word_pos(), last_block + 3);
+    for _ in 3..16 {
+        rng.next_u32();
+    }
+    assert_eq!(rng.get_word_pos(), 0);
+    assert_eq!(next_block(&mut rng), first);
+}
+
+#[test]
+fn test_rng_set_stream_mid_block() {
+    let mut rng = ChaCha8Rng::from_seed([7; 32]);
+    let mut other = rng.clone();
+
+    rng.set_stream(51);
+    for _ in 0..7 {
+        assert_ne!(rng.next_u32(), other.next_u32());
+    }
+    other.set_stream(51);
+    assert_eq!(other.get_word_pos(), 7);
+    for _ in 7..40 {
+        assert_eq!(rng.next_u32(), other.next_u32());
+    }
+}
+
+#[test]
+fn test_rng_reduced_rounds() {
+    // Checked against the rand_chacha crate.
+    let mut seed = [0u8; 32];
+    for (i, b) in seed.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+
+    assert_eq!(
+        ChaCha8Rng::from_seed(seed).next_u64(),
+        0x6aab_126e_8fb2_1540
+    );
+    assert_eq!(
+        ChaCha12Rng::from_seed(seed).next_u64(),
+        0x5ec6_7ad1_fff9_31f2
+    );
+}
+
+#[test]
+fn test_rng_split() {
+    let rng = ChaCha20Rng::from_seed([3; 32]);
+    let mut a = rng.split(1);
+    let mut b = rng.split(2);
+    assert_eq!((a.get_stream(), a.get_word_pos()), (1, 0));
+
+    let mut same = ChaCha20Rng::from_seed([3; 32]);
+    same.set_stream(1);
+    let block_a = next_block(&mut a);
+    assert_eq!(block_a, next_block(&mut same));
+    assert_ne!(block_a, next_block(&mut b));
+
+    // Splitting ignores the parent's position.
+    let mut parent = rng.split(1);
+    parent.jump(100);
+    assert_eq!(next_block(&mut parent.split(1)), block_a);
+}
+
+#[test]
+fn test_rng_jump() {
+    let mut rng = ChaCha12Rng::from_seed([9; 32]);
+    let words: Vec<u32> = (0..100).map(|_| rng.next_u32()).collect();
+
+    let mut jumper = ChaCha12Rng::from_seed([9; 32]);
+    jumper.jump(3);
+    assert_eq!(jumper.next_u32(), words[3]);
+    jumper.jump(40);
+    assert_eq!(jumper.next_u32(), words[44]);
+    jumper.jump(0);
+    assert_eq!(jumper.next_u32(), words[45]);
+    jumper.jump((1 << 68) - 46);
+    assert_eq!(jumper.get_word_pos(), 0);
+    assert_eq!(jumper.next_u32(), words[0]);
+}
+
+#[cfg(all(test, feature = "alloc"))]
+fn golden_output<const ROUNDS: usize>(seed: [u8; 32], stream: u64, word_pos: u128) -> Vec<u8> {
+    let mut rng = ChaChaRng::<ROUNDS>::from_seed(seed).split(stream);
+    rng.jump(word_pos);
+
+    let mut out = vec![0; 64];
+    rng.fill_bytes(&mut out);
+    out
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_rng_golden() {
+    let golden = include_str!("../testdata/rng_golden.txt");
+
+    let mut checked = 0;
+    for line in golden.lines().filter(|l| !l.starts_with('#')) {
+        let fields: Vec<&str> = line.split(' ').collect();
+        assert_eq!(fields.len(), 5, "{}", line);
+
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(&decode_hex(fields[0]).unwrap());
+        let stream: u64 = fields[2].parse().unwrap();
+        let word_pos: u128 = fields[3].parse().unwrap();
+        let expected = decode_hex(fields[4]).unwrap();
+
+        let actual = match fields[1] {
+            "8" => golden_output::<8>(seed, stream, word_pos),
+            "12" => golden_output::<12>(seed, stream, word_pos),
+            "20" => golden_output::<20>(seed, stream, word_pos),
+            rounds => panic!("unknown round count {}", rounds),
+        };
+        assert_eq!(actual, expected, "{}", line);
+        checked += 1;
+    }
+
+    assert_eq!(checked, 36);
+}
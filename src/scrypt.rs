@@ -0,0 +1,354 @@
+// RFC 7914  The scrypt Password-Based Key Derivation Function
+//
+// scrypt makes deriving a key expensive in memory as well as time, so that
+// guessing passphrases on custom hardware gains little.  Its core is
+// Salsa20/8, the 8-round Salsa20 core that ChaCha was derived from: the same
+// add, rotate and XOR operations on a 4x4 matrix of words, arranged
+// differently.
+
+use crate::error::ChaChaError;
+use crate::sha256::pbkdf2_hmac_sha256;
+use crate::wipe::wipe;
+use alloc::vec;
+
+/// The cost parameters of scrypt: the CPU/memory cost N = 2^`log_n`, the
+/// block size `r` and the parallelization `p`.  A derivation uses
+/// 128 * r * (N + p + 2) bytes of memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScryptParams {
+    log_n: u8,
+    r: u32,
+    p: u32,
+}
+
+impl ScryptParams {
+    /// Fails with `InvalidScryptParams` unless 1 <= `log_n` < 16 * `r`,
+    /// `r` and `p` are at least 1, r * p < 2^30, and the memory needed
+    /// fits in the address space.
+    pub fn new(log_n: u8, r: u32, p: u32) -> Result<ScryptParams, ChaChaError> {
+        //    The CPU/Memory cost parameter N ("costParameter") must be larger
+        //    than 1, a power of 2, and less than 2^(128 * r / 8).  The
+        //    parallelization parameter p ("parallelizationParameter") is a
+        //    positive integer less than or equal to ((2^32-1) * 32) / (128 *
+        //    r).
+        //
+        // The bound on p is the same as r * p < 2^30.  The arrays must also
+        // fit in memory together: N blocks of 128 * r bytes for V, p for B
+        // and two of scratch, which also keeps `memory` from overflowing.
+        let valid = log_n >= 1
+            && r >= 1
+            && p >= 1
+            && u64::from(log_n) < 16 * u64::from(r)
+            && u64::from(r) * u64::from(p) < 1 << 30
+            && u32::from(log_n) < usize::BITS
+            && (1usize << log_n)
+                .checked_add(p as usize + 2)
+                .and_then(|blocks| blocks.checked_mul(128))
+                .and_then(|len| len.checked_mul(r as usize))
+                .is_some();
+
+        if valid {
+            Ok(ScryptParams { log_n, r, p })
+        } else {
+            Err(ChaChaError::InvalidScryptParams)
+        }
+    }
+
+    pub fn log_n(&self) -> u8 {
+        self.log_n
+    }
+
+    pub fn r(&self) -> u32 {
+        self.r
+    }
+
+    pub fn p(&self) -> u32 {
+        self.p
+    }
+
+    /// The bytes of memory a derivation with these parameters uses: N
+    /// blocks of 128 * r bytes for V, p for B and two more of scratch.
+    pub fn memory(&self) -> u64 {
+        128 * u64::from(self.r) * ((1 << self.log_n) + u64::from(self.p) + 2)
+    }
+}
+
+impl Default for ScryptParams {
+    /// N = 2^15, r = 8, p = 1: 32 MiB and a fraction of a second per
+    /// derivation on current hardware.
+    fn default() -> ScryptParams {
+        ScryptParams {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+/// Derives `out.len()` bytes of key from `passphrase` and `salt` with
+/// scrypt.
+///
+/// Fails with `InvalidScryptParams` if `out` is longer than
+/// (2^32 - 1) * 32 bytes.
+pub fn scrypt(
+    passphrase: &[u8],
+    salt: &[u8],
+    params: &ScryptParams,
+    out: &mut [u8],
+) -> Result<(), ChaChaError> {
+    if out.len() as u64 > u64::from(u32::MAX) * 32 {
+        return Err(ChaChaError::InvalidScryptParams);
+    }
+
+    let r = params.r as usize;
+    let n = 1usize << params.log_n;
+    let block_len = 128 * r;
+
+    //    1. Initialize an array B consisting of p blocks of 128 * r octets
+    //       each:
+    //        B[0] || B[1] || ... || B[p - 1] =
+    //          PBKDF2-HMAC-SHA256 (P, S, 1, p * 128 * r)
+    let mut b = vec![0u8; params.p as usize * block_len];
+    pbkdf2_hmac_sha256(passphrase, salt, 1, &mut b);
+
+    //    2. for i = 0 to p - 1 do
+    //          B[i] = scryptROMix (r, B[i], N)
+    //       end for
+    let mut x = vec![0u32; block_len / 4];
+    let mut y = vec![0u32; block_len / 4];
+    let mut v = vec![0u32; n * block_len / 4];
+    for block in b.chunks_exact_mut(block_len) {
+        for (word, bytes) in x.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        ro_mix(&mut x, &mut y, &mut v);
+        for (bytes, word) in block.chunks_exact_mut(4).zip(x.iter()) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    //    3. DK = PBKDF2-HMAC-SHA256 (P, B, 1, dkLen)
+    pbkdf2_hmac_sha256(passphrase, &b, 1, out);
+
+    wipe(&mut b);
+    wipe(&mut x);
+    wipe(&mut y);
+    wipe(&mut v);
+    Ok(())
+}
+
+macro_rules! salsa_quarter_round {
+    ($x: ident, $a: literal, $b: literal, $c: literal, $d: literal) => {{
+        $x[$b] ^= $x[$a].wrapping_add($x[$d]).rotate_left(7);
+        $x[$c] ^= $x[$b].wrapping_add($x[$a]).rotate_left(9);
+        $x[$d] ^= $x[$c].wrapping_add($x[$b]).rotate_left(13);
+        $x[$a] ^= $x[$d].wrapping_add($x[$c]).rotate_left(18);
+    }};
+}
+
+// 3.  The Salsa20/8 Core Function
+fn salsa20_8(b: &mut [u32; 16]) {
+    let mut x = *b;
+    for _ in 0..4 {
+        // Columns.
+        salsa_quarter_round!(x, 0, 4, 8, 12);
+        salsa_quarter_round!(x, 5, 9, 13, 1);
+        salsa_quarter_round!(x, 10, 14, 2, 6);
+        salsa_quarter_round!(x, 15, 3, 7, 11);
+        // Rows.
+        salsa_quarter_round!(x, 0, 1, 2, 3);
+        salsa_quarter_round!(x, 5, 6, 7, 4);
+        salsa_quarter_round!(x, 10, 11, 8, 9);
+        salsa_quarter_round!(x, 15, 12, 13, 14);
+    }
+
+    for (b, x) in b.iter_mut().zip(x.iter()) {
+        *b = b.wrapping_add(*x);
+    }
+    wipe(&mut x);
+}
+
+// 4.  The scryptBlockMix Algorithm, from `b` into `y`.  Both hold 2 * r
+// Salsa20/8 blocks of 16 words.
+fn block_mix(b: &[u32], y: &mut [u32]) {
+    let blocks = b.len() / 16;
+    let r = blocks / 2;
+
+    //    1. X = B[2 * r - 1]
+    let mut x = [0u32; 16];
+    x.copy_from_slice(&b[(blocks - 1) * 16..]);
+
+    //    2. for i = 0 to 2 * r - 1 do
+    //         T = X xor B[i]
+    //         X = Salsa (T)
+    //         Y[i] = X
+    //       end for
+    //
+    //    3. B' = (Y[0], Y[2], ..., Y[2 * r - 2],
+    //             Y[1], Y[3], ..., Y[2 * r - 1])
+    for (i, block) in b.chunks_exact(16).enumerate() {
+        for (x, b) in x.iter_mut().zip(block.iter()) {
+            *x ^= b;
+        }
+        salsa20_8(&mut x);
+        let j = i / 2 + (i % 2) * r;
+        y[j * 16..j * 16 + 16].copy_from_slice(&x);
+    }
+    wipe(&mut x);
+}
+
+// 5.  The scryptROMix Algorithm, in place on `x`.  `y` is scratch space of
+// the same size and `v` holds the N copies of it.
+fn ro_mix(x: &mut [u32], y: &mut [u32], v: &mut [u32]) {
+    let len = x.len();
+    let n = v.len() / len;
+
+    //    2. for i = 0 to N - 1 do
+    //         V[i] = X
+    //         X = scryptBlockMix (X)
+    //       end for
+    for chunk in v.chunks_exact_mut(len) {
+        chunk.copy_from_slice(x);
+        block_mix(x, y);
+        x.copy_from_slice(y);
+    }
+
+    //    3. for i = 0 to N - 1 do
+    //         j = Integerify (X) mod N
+    //                where Integerify (B[0] ... B[2 * r - 1]) is defined
+    //                as the result of interpreting B[2 * r - 1] as a
+    //                little-endian integer.
+    //         T = X xor V[j]
+    //         X = scryptBlockMix (T)
+    //       end for
+    for _ in 0..n {
+        let last = len - 16;
+        let integer = u64::from(x[last]) | u64::from(x[last + 1]) << 32;
+        let j = (integer & (n as u64 - 1)) as usize;
+        for (x, v) in x.iter_mut().zip(v[j * len..(j + 1) * len].iter()) {
+            *x ^= v;
+        }
+        block_mix(x, y);
+        x.copy_from_slice(y);
+    }
+}
+
+#[cfg(test)]
+fn scrypt_64(passphrase: &[u8], salt: &[u8], log_n: u8, r: u32, p: u32) -> [u8; 64] {
+    let params = ScryptParams::new(log_n, r, p).unwrap();
+    let mut out = [0u8; 64];
+    scrypt(passphrase, salt, &params, &mut out).unwrap();
+    out
+}
+
+// 8.  Test Vectors for Salsa20/8 Core
+
+#[test]
+fn test_salsa20_8() {
+    let input = [
+        0x7e, 0x87, 0x9a, 0x21, 0x4f, 0x3e, 0xc9, 0x86, 0x7c, 0xa9, 0x40, 0xe6, 0x41, 0x71, 0x8f,
+        0x26, 0xba, 0xee, 0x55, 0x5b, 0x8c, 0x61, 0xc1, 0xb5, 0x0d, 0xf8, 0x46, 0x11, 0x6d, 0xcd,
+        0x3b, 0x1d, 0xee, 0x24, 0xf3, 0x19, 0xdf, 0x9b, 0x3d, 0x85, 0x14, 0x12, 0x1e, 0x4b, 0x5a,
+        0xc5, 0xaa, 0x32, 0x76, 0x02, 0x1d, 0x29, 0x09, 0xc7, 0x48, 0x29, 0xed, 0xeb, 0xc6, 0x8d,
+        0xb8, 0xb8, 0xc2, 0x5e,
+    ];
+    let expected = [
+        0xa4, 0x1f, 0x85, 0x9c, 0x66, 0x08, 0xcc, 0x99, 0x3b, 0x81, 0xca, 0xcb, 0x02, 0x0c, 0xef,
+        0x05, 0x04, 0x4b, 0x21, 0x81, 0xa2, 0xfd, 0x33, 0x7d, 0xfd, 0x7b, 0x1c, 0x63, 0x96, 0x68,
+        0x2f, 0x29, 0xb4, 0x39, 0x31, 0x68, 0xe3, 0xc9, 0xe6, 0xbc, 0xfe, 0x6b, 0xc5, 0xb7, 0xa0,
+        0x6d, 0x96, 0xba, 0xe4, 0x24, 0xcc, 0x10, 0x2c, 0x91, 0x74, 0x5c, 0x24, 0xad, 0x67, 0x3d,
+        0xc7, 0x61, 0x8f, 0x81,
+    ];
+
+    let mut b = [0u32; 16];
+    for (word, bytes) in b.iter_mut().zip(input.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    salsa20_8(&mut b);
+
+    let mut output = [0u8; 64];
+    for (bytes, word) in output.chunks_exact_mut(4).zip(b.iter()) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    assert_eq!(output[..], expected[..]);
+}
+
+// 12.  Test Vectors for scrypt
+//
+// The last vector, N = 1048576, needs 1 GiB of memory and is left out.
+
+#[test]
+fn test_scrypt_vector_1() {
+    //    scrypt (P="", S="",
+    //            N=16, r=1, p=1, dklen=64) =
+    let expected = [
+        0x77, 0xd6, 0x57, 0x62, 0x38, 0x65, 0x7b, 0x20, 0x3b, 0x19, 0xca, 0x42, 0xc1, 0x8a, 0x04,
+        0x97, 0xf1, 0x6b, 0x48, 0x44, 0xe3, 0x07, 0x4a, 0xe8, 0xdf, 0xdf, 0xfa, 0x3f, 0xed, 0xe2,
+        0x14, 0x42, 0xfc, 0xd0, 0x06, 0x9d, 0xed, 0x09, 0x48, 0xf8, 0x32, 0x6a, 0x75, 0x3a, 0x0f,
+        0xc8, 0x1f, 0x17, 0xe8, 0xd3, 0xe0, 0xfb, 0x2e, 0x0d, 0x36, 0x28, 0xcf, 0x35, 0xe2, 0x0c,
+        0x38, 0xd1, 0x89, 0x06,
+    ];
+    assert_eq!(scrypt_64(b"", b"", 4, 1, 1)[..], expected[..]);
+}
+
+#[test]
+fn test_scrypt_vector_2() {
+    //    scrypt (P="password", S="NaCl",
+    //            N=1024, r=8, p=16, dkLen=64) =
+    let expected = [
+        0xfd, 0xba, 0xbe, 0x1c, 0x9d, 0x34, 0x72, 0x00, 0x78, 0x56, 0xe7, 0x19, 0x0d, 0x01, 0xe9,
+        0xfe, 0x7c, 0x6a, 0xd7, 0xcb, 0xc8, 0x23, 0x78, 0x30, 0xe7, 0x73, 0x76, 0x63, 0x4b, 0x37,
+        0x31, 0x62, 0x2e, 0xaf, 0x30, 0xd9, 0x2e, 0x22, 0xa3, 0x88, 0x6f, 0xf1, 0x09, 0x27, 0x9d,
+        0x98, 0x30, 0xda, 0xc7, 0x27, 0xaf, 0xb9, 0x4a, 0x83, 0xee, 0x6d, 0x83, 0x60, 0xcb, 0xdf,
+        0xa2, 0xcc, 0x06, 0x40,
+    ];
+    assert_eq!(scrypt_64(b"password", b"NaCl", 10, 8, 16)[..], expected[..]);
+}
+
+#[test]
+fn test_scrypt_vector_3() {
+    //    scrypt (P="pleaseletmein", S="SodiumChloride",
+    //            N=16384, r=8, p=1, dkLen=64) =
+    let expected = [
+        0x70, 0x23, 0xbd, 0xcb, 0x3a, 0xfd, 0x73, 0x48, 0x46, 0x1c, 0x06, 0xcd, 0x81, 0xfd, 0x38,
+        0xeb, 0xfd, 0xa8, 0xfb, 0xba, 0x90, 0x4f, 0x8e, 0x3e, 0xa9, 0xb5, 0x43, 0xf6, 0x54, 0x5d,
+        0xa1, 0xf2, 0xd5, 0x43, 0x29, 0x55, 0x61, 0x3f, 0x0f, 0xcf, 0x62, 0xd4, 0x97, 0x05, 0x24,
+        0x2a, 0x9a, 0xf9, 0xe6, 0x1e, 0x85, 0xdc, 0x0d, 0x65, 0x1e, 0x40, 0xdf, 0xcf, 0x01, 0x7b,
+        0x45, 0x57, 0x58, 0x87,
+    ];
+    assert_eq!(
+        scrypt_64(b"pleaseletmein", b"SodiumChloride", 14, 8, 1)[..],
+        expected[..]
+    );
+}
+
+#[test]
+fn test_scrypt_params() {
+    assert!(ScryptParams::new(1, 1, 1).is_ok());
+    assert!(ScryptParams::new(15, 1, 1).is_ok());
+    assert_eq!(ScryptParams::default().memory(), (32 << 20) + 3 * 1024);
+    assert_eq!(
+        ScryptParams::new(1, 1, 1 << 29).unwrap().memory(),
+        (1 << 36) + 512
+    );
+
+    for &(log_n, r, p) in [
+        (0, 8, 1),
+        (4, 0, 1),
+        (4, 8, 0),
+        (16, 1, 1),
+        (10, 1 << 15, 1 << 15),
+        (28, 4 * ((1 << 27) - 1), 1),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            ScryptParams::new(log_n, r, p),
+            Err(ChaChaError::InvalidScryptParams),
+            "{} {} {}",
+            log_n,
+            r,
+            p
+        );
+    }
+}
@@ -0,0 +1,376 @@
+// SHA-256 (FIPS 180-4), HMAC-SHA-256 (RFC 2104) and PBKDF2-HMAC-SHA-256
+// (RFC 8018 section 5.2), which scrypt uses to spread the passphrase and
+// salt over its input blocks and to condense its output.
+
+use crate::wipe::wipe;
+
+// 4.2.2  SHA-224 and SHA-256 Constants
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+// 5.3.3  SHA-256
+const INITIAL_HASH: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// The block size of SHA-256, which is also the HMAC key block size.
+const BLOCK_LEN: usize = 64;
+
+// Incremental SHA-256.  The buffered input and the chaining value are wiped
+// when the value is dropped, since both may derive from a passphrase.
+#[derive(Clone)]
+pub(crate) struct Sha256 {
+    state: [u32; 8],
+    buffer: [u8; BLOCK_LEN],
+    leftover: usize,
+    // The message length so far, in bytes.
+    len: u64,
+}
+
+impl Drop for Sha256 {
+    fn drop(&mut self) {
+        wipe(&mut self.state);
+        wipe(&mut self.buffer);
+    }
+}
+
+impl Sha256 {
+    pub(crate) fn new() -> Sha256 {
+        Sha256 {
+            state: INITIAL_HASH,
+            buffer: [0; BLOCK_LEN],
+            leftover: 0,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        let mut data = data;
+        self.len += data.len() as u64;
+
+        if self.leftover > 0 {
+            let n = (BLOCK_LEN - self.leftover).min(data.len());
+            self.buffer[self.leftover..self.leftover + n].copy_from_slice(&data[..n]);
+            self.leftover += n;
+            data = &data[n..];
+
+            if self.leftover < BLOCK_LEN {
+                return;
+            }
+            compress(&mut self.state, &self.buffer);
+            self.leftover = 0;
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_LEN);
+        for block in &mut blocks {
+            compress(&mut self.state, block);
+        }
+
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.leftover = rest.len();
+    }
+
+    pub(crate) fn finalize(mut self) -> [u8; 32] {
+        // 5.1.1  SHA-1, SHA-224 and SHA-256
+        //    Suppose that the length of the message, M, is l bits.  Append the
+        //    bit "1" to the end of the message, followed by k zero bits, where
+        //    k is the smallest, non-negative solution to the equation
+        //    l + 1 + k = 448 mod 512.  Then append the 64-bit block that is
+        //    equal to the number l expressed using a binary representation.
+        let bits = self.len * 8;
+        let mut padding = [0u8; BLOCK_LEN + 8];
+        padding[0] = 0x80;
+        let zeros = (BLOCK_LEN + 55 - self.leftover) % BLOCK_LEN;
+        padding[1 + zeros..9 + zeros].copy_from_slice(&bits.to_be_bytes());
+        self.update(&padding[..9 + zeros]);
+
+        let mut digest = [0u8; 32];
+        for (out, word) in digest.chunks_exact_mut(4).zip(self.state.iter()) {
+            out.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+// 6.2.2  SHA-256 Hash Computation, for one 512-bit block.
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    for (w, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *w = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for t in 16..64 {
+        let s0 = w[t - 15].rotate_right(7) ^ w[t - 15].rotate_right(18) ^ (w[t - 15] >> 3);
+        let s1 = w[t - 2].rotate_right(17) ^ w[t - 2].rotate_right(19) ^ (w[t - 2] >> 10);
+        w[t] = w[t - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[t - 7])
+            .wrapping_add(s1);
+    }
+
+    let mut v = *state;
+    for (k, w) in K.iter().zip(w.iter()) {
+        let [a, b, c, d, e, f, g, h] = v;
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(*k)
+            .wrapping_add(*w);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        v = [t1.wrapping_add(t2), a, b, c, d.wrapping_add(t1), e, f, g];
+    }
+
+    for (s, v) in state.iter_mut().zip(v.iter()) {
+        *s = s.wrapping_add(*v);
+    }
+    wipe(&mut w);
+    wipe(&mut v);
+}
+
+// Computes the SHA-256 digest of `data`.
+#[cfg(test)]
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hash = Sha256::new();
+    hash.update(data);
+    hash.finalize()
+}
+
+// HMAC-SHA-256 with the key already absorbed: `inner` has hashed the key
+// XOR ipad and `outer` the key XOR opad, so each message costs only the
+// hashing of the message and of the inner digest.
+#[derive(Clone)]
+pub(crate) struct HmacSha256 {
+    inner: Sha256,
+    outer: Sha256,
+}
+
+impl HmacSha256 {
+    pub(crate) fn new(key: &[u8]) -> HmacSha256 {
+        //    (1) ... if K is longer than B bytes, first hash K, then append
+        //        zeros to the end of K to create a B byte string
+        let mut block = [0u8; BLOCK_LEN];
+        if key.len() > BLOCK_LEN {
+            let mut hash = Sha256::new();
+            hash.update(key);
+            block[..32].copy_from_slice(&hash.finalize());
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Sha256::new();
+        let mut outer = Sha256::new();
+        for b in block.iter_mut() {
+            *b ^= 0x36;
+        }
+        inner.update(&block);
+        for b in block.iter_mut() {
+            *b ^= 0x36 ^ 0x5c;
+        }
+        outer.update(&block);
+        wipe(&mut block);
+
+        HmacSha256 { inner, outer }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    pub(crate) fn finalize(self) -> [u8; 32] {
+        let HmacSha256 { inner, mut outer } = self;
+        let mut digest = inner.finalize();
+        outer.update(&digest);
+        wipe(&mut digest);
+        outer.finalize()
+    }
+}
+
+// Computes HMAC-SHA-256 of `data` under `key`.
+#[cfg(test)]
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = HmacSha256::new(key);
+    mac.update(data);
+    mac.finalize()
+}
+
+// 5.2.  PBKDF2, with HMAC-SHA-256 as the pseudorandom function.  Fills `out`
+// with key derived from `password` and `salt`; each 32-byte block costs
+// `iterations` HMAC computations.
+pub(crate) fn pbkdf2_hmac_sha256(password: &[u8], salt: &[u8], iterations: u32, out: &mut [u8]) {
+    let prf = HmacSha256::new(password);
+
+    for (i, chunk) in out.chunks_mut(32).enumerate() {
+        //    U_1 = PRF (P, S || INT (i)) ,
+        //    U_2 = PRF (P, U_1) ,
+        //    ...
+        //    T_i = U_1 \xor U_2 \xor ... \xor U_c
+        let mut mac = prf.clone();
+        mac.update(salt);
+        mac.update(&(i as u32 + 1).to_be_bytes());
+        let mut u = mac.finalize();
+        let mut t = u;
+
+        for _ in 1..iterations {
+            let mut mac = prf.clone();
+            mac.update(&u);
+            u = mac.finalize();
+            for (t, u) in t.iter_mut().zip(u.iter()) {
+                *t ^= u;
+            }
+        }
+
+        chunk.copy_from_slice(&t[..chunk.len()]);
+        wipe(&mut u);
+        wipe(&mut t);
+    }
+}
+
+// FIPS 180-2 appendix B, and the empty message.
+
+#[test]
+fn test_sha256_empty() {
+    let expected = [
+        0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99, 0x6f, 0xb9,
+        0x24, 0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b, 0x78, 0x52,
+        0xb8, 0x55,
+    ];
+    assert_eq!(sha256(b""), expected);
+}
+
+#[test]
+fn test_sha256_abc() {
+    let expected = [
+        0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae, 0x22,
+        0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61, 0xf2, 0x00,
+        0x15, 0xad,
+    ];
+    assert_eq!(sha256(b"abc"), expected);
+}
+
+#[test]
+fn test_sha256_two_blocks() {
+    let expected = [
+        0x24, 0x8d, 0x6a, 0x61, 0xd2, 0x06, 0x38, 0xb8, 0xe5, 0xc0, 0x26, 0x93, 0x0c, 0x3e, 0x60,
+        0x39, 0xa3, 0x3c, 0xe4, 0x59, 0x64, 0xff, 0x21, 0x67, 0xf6, 0xec, 0xed, 0xd4, 0x19, 0xdb,
+        0x06, 0xc1,
+    ];
+    let msg = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    assert_eq!(sha256(msg), expected);
+
+    // The same message fed in pieces of every size.
+    for chunk in 1..=msg.len() {
+        let mut hash = Sha256::new();
+        for piece in msg.chunks(chunk) {
+            hash.update(piece);
+        }
+        assert_eq!(hash.finalize(), expected, "chunk size {}", chunk);
+    }
+}
+
+#[test]
+fn test_sha256_million_a() {
+    let expected = [
+        0xcd, 0xc7, 0x6e, 0x5c, 0x99, 0x14, 0xfb, 0x92, 0x81, 0xa1, 0xc7, 0xe2, 0x84, 0xd7, 0x3e,
+        0x67, 0xf1, 0x80, 0x9a, 0x48, 0xa4, 0x97, 0x20, 0x0e, 0x04, 0x6d, 0x39, 0xcc, 0xc7, 0x11,
+        0x2c, 0xd0,
+    ];
+    let mut hash = Sha256::new();
+    for _ in 0..1000 {
+        hash.update(&[b'a'; 1000]);
+    }
+    assert_eq!(hash.finalize(), expected);
+}
+
+// RFC 4231  Identifiers and Test Vectors for HMAC-SHA-224, HMAC-SHA-256,
+// HMAC-SHA-384, and HMAC-SHA-512
+
+#[test]
+fn test_hmac_sha256_case_1() {
+    let expected = [
+        0xb0, 0x34, 0x4c, 0x61, 0xd8, 0xdb, 0x38, 0x53, 0x5c, 0xa8, 0xaf, 0xce, 0xaf, 0x0b, 0xf1,
+        0x2b, 0x88, 0x1d, 0xc2, 0x00, 0xc9, 0x83, 0x3d, 0xa7, 0x26, 0xe9, 0x37, 0x6c, 0x2e, 0x32,
+        0xcf, 0xf7,
+    ];
+    assert_eq!(hmac_sha256(&[0x0b; 20], b"Hi There"), expected);
+}
+
+#[test]
+fn test_hmac_sha256_case_2() {
+    let expected = [
+        0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95, 0x75,
+        0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9, 0x64, 0xec,
+        0x38, 0x43,
+    ];
+    assert_eq!(
+        hmac_sha256(b"Jefe", b"what do ya want for nothing?"),
+        expected
+    );
+}
+
+#[test]
+fn test_hmac_sha256_case_6() {
+    // A key longer than the block size is hashed first.
+    let expected = [
+        0x60, 0xe4, 0x31, 0x59, 0x1e, 0xe0, 0xb6, 0x7f, 0x0d, 0x8a, 0x26, 0xaa, 0xcb, 0xf5, 0xb7,
+        0x7f, 0x8e, 0x0b, 0xc6, 0x21, 0x37, 0x28, 0xc5, 0x14, 0x05, 0x46, 0x04, 0x0f, 0x0e, 0xe3,
+        0x7f, 0x54,
+    ];
+    assert_eq!(
+        hmac_sha256(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First"
+        ),
+        expected
+    );
+}
+
+// RFC 7914  11.  Test Vectors for PBKDF2 with HMAC-SHA-256
+
+#[test]
+fn test_pbkdf2_hmac_sha256_vector_1() {
+    //    PBKDF2-HMAC-SHA-256 (P="passwd", S="salt",
+    //                         c=1, dkLen=64) =
+    let expected = [
+        0x55, 0xac, 0x04, 0x6e, 0x56, 0xe3, 0x08, 0x9f, 0xec, 0x16, 0x91, 0xc2, 0x25, 0x44, 0xb6,
+        0x05, 0xf9, 0x41, 0x85, 0x21, 0x6d, 0xde, 0x04, 0x65, 0xe6, 0x8b, 0x9d, 0x57, 0xc2, 0x0d,
+        0xac, 0xbc, 0x49, 0xca, 0x9c, 0xcc, 0xf1, 0x79, 0xb6, 0x45, 0x99, 0x16, 0x64, 0xb3, 0x9d,
+        0x77, 0xef, 0x31, 0x7c, 0x71, 0xb8, 0x45, 0xb1, 0xe3, 0x0b, 0xd5, 0x09, 0x11, 0x20, 0x41,
+        0xd3, 0xa1, 0x97, 0x83,
+    ];
+    let mut out = [0u8; 64];
+    pbkdf2_hmac_sha256(b"passwd", b"salt", 1, &mut out);
+    assert_eq!(out[..], expected[..]);
+
+    // A shorter output is a prefix of the longer one.
+    let mut short = [0u8; 40];
+    pbkdf2_hmac_sha256(b"passwd", b"salt", 1, &mut short);
+    assert_eq!(short[..], expected[..40]);
+}
+
+#[test]
+fn test_pbkdf2_hmac_sha256_vector_2() {
+    //    PBKDF2-HMAC-SHA-256 (P="Password", S="NaCl",
+    //                         c=80000, dkLen=64) =
+    let expected = [
+        0x4d, 0xdc, 0xd8, 0xf6, 0x0b, 0x98, 0xbe, 0x21, 0x83, 0x0c, 0xee, 0x5e, 0xf2, 0x27, 0x01,
+        0xf9, 0x64, 0x1a, 0x44, 0x18, 0xd0, 0x4c, 0x04, 0x14, 0xae, 0xff, 0x08, 0x87, 0x6b, 0x34,
+        0xab, 0x56, 0xa1, 0xd4, 0x25, 0xa1, 0x22, 0x58, 0x33, 0x54, 0x9a, 0xdb, 0x84, 0x1b, 0x51,
+        0xc9, 0xb3, 0x17, 0x6a, 0x27, 0x2b, 0xde, 0xbb, 0xa1, 0xd0, 0x78, 0x47, 0x8f, 0x62, 0xb3,
+        0x97, 0xf3, 0x3c, 0x8d,
+    ];
+    let mut out = [0u8; 64];
+    pbkdf2_hmac_sha256(b"Password", b"NaCl", 80000, &mut out);
+    assert_eq!(out[..], expected[..]);
+}
@@ -0,0 +1,39 @@
+// Clearing of keys, key stream and working state.
+//
+// Zeroing memory that is about to be freed or go out of scope is a dead
+// store that the compiler is free to remove, so the zeros are written with
+// volatile writes, and a fence keeps later code from being moved ahead of
+// them.
+
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// Overwrites every element of `data` with its default value, zero for the
+/// integer types, in a way the compiler will not optimize away.  For
+/// clearing copies of keys and passphrases before they are freed.
+pub fn wipe<T: Copy + Default>(data: &mut [T]) {
+    wipe_with(data, T::default())
+}
+
+// Overwrites every element of `data` with `zero`, for types such as SIMD
+// vectors that have no `Default`.
+pub(crate) fn wipe_with<T: Copy>(data: &mut [T], zero: T) {
+    for x in data.iter_mut() {
+        // Safety: `x` is a valid, aligned reference.
+        unsafe { ptr::write_volatile(x, zero) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+#[test]
+fn test_wipe() {
+    let mut bytes = [0xa5u8; 37];
+    wipe(&mut bytes);
+    assert_eq!(bytes, [0; 37]);
+
+    let mut words = [u32::MAX; 16];
+    wipe(&mut words[4..12]);
+    assert_eq!(words[..4], [u32::MAX; 4]);
+    assert_eq!(words[4..12], [0; 8]);
+    assert_eq!(words[12..], [u32::MAX; 4]);
+}